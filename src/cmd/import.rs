@@ -0,0 +1,443 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Context;
+use clap::ValueEnum;
+use k8s_openapi::api::core::v1::Secret;
+use serde::Deserialize;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+use crate::save::Envelope;
+use crate::save::SaveMethods;
+use crate::vault::models::sys::init::PostInitResponse;
+use crate::vault::models::sys::seal_status::GetSealStatusResponse;
+use crate::vault::VaultClient;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// Detect the format from the input.
+    Auto,
+    /// Output of `vault operator init -format=json`.
+    VaultCli,
+    /// A secret holding one key per entry, as written by bank-vaults.
+    BankVaults,
+    /// Init data as stored by vault-init.
+    VaultInit,
+}
+
+/// Where init data to import is read from.
+pub enum ImportSource<'a> {
+    /// A file, or stdin if not set. A directory is read as one entry per file.
+    Input(Option<&'a Path>),
+    /// A Kubernetes secret, read as one entry per key.
+    KubeSecret {
+        name: &'a str,
+        namespace: Option<&'a str>,
+    },
+}
+
+/// Output of `vault operator init -format=json`.
+#[derive(Deserialize)]
+struct VaultCliOutput {
+    #[serde(default)]
+    unseal_keys_b64: Vec<String>,
+    #[serde(default)]
+    unseal_keys_hex: Vec<String>,
+    unseal_shares: Option<i64>,
+    unseal_threshold: Option<i64>,
+    #[serde(default)]
+    recovery_keys_b64: Vec<String>,
+    #[serde(default)]
+    recovery_keys_hex: Vec<String>,
+    recovery_keys_shares: Option<i64>,
+    recovery_keys_threshold: Option<i64>,
+    root_token: String,
+}
+
+/// Reads init data created by hand or by other tools and writes it to the
+/// save methods, so that vault-init can take over an existing cluster.
+pub async fn import(
+    vault: &VaultClient,
+    save_methods: &SaveMethods,
+    source: ImportSource<'_>,
+    format: ImportFormat,
+    key_prefix: &str,
+    force: bool,
+) -> anyhow::Result<()> {
+    let phase = "import";
+
+    let mut envelope = match source {
+        ImportSource::Input(Some(path)) if path.is_dir() => {
+            check_format(format, ImportFormat::BankVaults)?;
+            from_entries(&read_dir(path)?, key_prefix)?
+        }
+        ImportSource::Input(input) => {
+            let bytes = match input {
+                Some(path) => std::fs::read(path)
+                    .with_context(|| format!("Failed reading input file: {}", path.display()))?,
+                None => std::io::read_to_string(std::io::stdin())?.into_bytes(),
+            };
+            parse(&bytes, format, key_prefix)?
+        }
+        ImportSource::KubeSecret { name, namespace } => {
+            check_format(format, ImportFormat::BankVaults)?;
+            from_entries(&read_kube_secret(name, namespace).await?, key_prefix)?
+        }
+    };
+    let data = envelope.data()?;
+    info!(
+        phase,
+        keys = data.keys.len(),
+        recovery_keys = data.recovery_keys.len(),
+        "Read init data to import"
+    );
+
+    // Save methods that cannot be read may hold init data too, so only those
+    // found to hold none are written without --force
+    match save_methods.hold_init().await {
+        Ok(false) => {}
+        Ok(true) if force => warn!(phase, "Replacing init data held by save methods"),
+        Err(err) if force => warn!(phase, ?err, "Failed checking save methods for init data"),
+        Ok(true) => {
+            let msg = "Save methods already hold init data, use --force to replace it";
+            error!(phase, msg);
+            anyhow::bail!(msg);
+        }
+        Err(err) => {
+            error!(
+                phase,
+                "Failed checking save methods for init data, use --force to replace it"
+            );
+            return Err(err);
+        }
+    }
+
+    let seal_status = vault.get_seal_status().await.inspect_err(|_| {
+        error!(phase, "Failed checking seal status");
+    })?;
+    check_matches(&envelope, &seal_status).inspect_err(|err| {
+        error!(phase, %err, "Imported init data does not match Vault");
+    })?;
+    envelope.metadata.update(&seal_status);
+    envelope.metadata.server_pin_sha256 = vault.server_pin();
+
+    info!(phase, "Writing imported init data to save methods");
+    save_methods
+        .save_init_all(&envelope)
+        .await
+        .inspect_err(|_| {
+            error!(phase, "Failed writing imported init data to save methods");
+        })?;
+    info!(
+        phase,
+        "Successfully wrote imported init data to save methods"
+    );
+
+    Ok(())
+}
+
+/// Checks that imported init data has as many key shares as Vault was
+/// initialized with, of the kind it uses to unseal or generate root, and the
+/// same threshold if the input records it.
+fn check_matches(envelope: &Envelope, seal_status: &GetSealStatusResponse) -> anyhow::Result<()> {
+    if !seal_status.initialized {
+        anyhow::bail!("Vault is not initialized");
+    }
+    let data = envelope.data()?;
+    let (kind, keys) = if seal_status.recovery_seal() {
+        ("recovery keys", data.recovery_keys.len())
+    } else {
+        ("unseal keys", data.keys.len())
+    };
+    if i64::try_from(keys)? != seal_status.n {
+        anyhow::bail!(
+            "Input has {keys} {kind}, but Vault has {} key shares",
+            seal_status.n
+        );
+    }
+    if let Some(threshold) = envelope.metadata.secret_threshold {
+        if threshold != seal_status.t {
+            anyhow::bail!(
+                "Input has a threshold of {threshold}, but Vault has a threshold of {}",
+                seal_status.t
+            );
+        }
+    }
+    Ok(())
+}
+
+fn check_format(format: ImportFormat, expected: ImportFormat) -> anyhow::Result<()> {
+    if format != ImportFormat::Auto && format != expected {
+        anyhow::bail!("Input can only be read as {expected:?}, not {format:?}");
+    }
+    Ok(())
+}
+
+/// Parses init data from a single document in the given format.
+fn parse(bytes: &[u8], format: ImportFormat, key_prefix: &str) -> anyhow::Result<Envelope> {
+    let format = match format {
+        ImportFormat::Auto => detect(bytes, key_prefix)?,
+        format => format,
+    };
+    match format {
+        ImportFormat::VaultCli => from_vault_cli(serde_json::from_slice(bytes)?),
+        ImportFormat::BankVaults => from_entries(&entries_from_json(bytes)?, key_prefix),
+        ImportFormat::VaultInit | ImportFormat::Auto => Envelope::from_slice(bytes),
+    }
+}
+
+/// Guesses the format of a document from the fields it has.
+fn detect(bytes: &[u8], key_prefix: &str) -> anyhow::Result<ImportFormat> {
+    let value: serde_json::Value =
+        serde_json::from_slice(bytes).context("Input is not valid JSON")?;
+    let object = value.as_object().context("Input is not a JSON object")?;
+
+    let format = if ["unseal_keys_b64", "unseal_keys_hex", "recovery_keys_b64"]
+        .iter()
+        .any(|field| object.contains_key(*field))
+    {
+        ImportFormat::VaultCli
+    } else if object.get("kind").and_then(|kind| kind.as_str()) == Some("Secret")
+        || object.contains_key(&format!("{key_prefix}-root"))
+    {
+        ImportFormat::BankVaults
+    } else {
+        ImportFormat::VaultInit
+    };
+    info!(phase = "import", ?format, "Detected input format");
+    Ok(format)
+}
+
+fn from_vault_cli(output: VaultCliOutput) -> anyhow::Result<Envelope> {
+    let (keys, keys_base64) = normalize(&output.unseal_keys_hex, &output.unseal_keys_b64)?;
+    let (recovery_keys, recovery_keys_base64) =
+        normalize(&output.recovery_keys_hex, &output.recovery_keys_b64)?;
+    let mut envelope = imported(PostInitResponse {
+        keys,
+        keys_base64,
+        recovery_keys,
+        recovery_keys_base64,
+        root_token: output.root_token,
+    });
+
+    // Auto Unseal clusters report the recovery key shares in the seal status
+    let metadata = &mut envelope.metadata;
+    if output.recovery_keys_shares.unwrap_or(0) > 0 {
+        metadata.secret_shares = output.recovery_keys_shares;
+        metadata.secret_threshold = output.recovery_keys_threshold;
+    } else {
+        metadata.secret_shares = output.unseal_shares;
+        metadata.secret_threshold = output.unseal_threshold;
+    }
+    Ok(envelope)
+}
+
+/// Builds init data from entries named `<prefix>-root`, `<prefix>-unseal-N`
+/// and `<prefix>-recovery-N`, as bank-vaults stores them.
+fn from_entries(entries: &BTreeMap<String, Vec<u8>>, key_prefix: &str) -> anyhow::Result<Envelope> {
+    let text = |name: &str| -> anyhow::Result<Option<String>> {
+        entries
+            .get(name)
+            .map(|value| Ok(std::str::from_utf8(value)?.trim().to_owned()))
+            .transpose()
+    };
+    let numbered = |kind: &str| -> anyhow::Result<Vec<String>> {
+        let mut values = Vec::new();
+        while let Some(value) = text(&format!("{key_prefix}-{kind}-{}", values.len()))? {
+            values.push(value);
+        }
+        Ok(values)
+    };
+
+    let root_token = text(&format!("{key_prefix}-root"))?
+        .with_context(|| format!("Input has no root token entry: {key_prefix}-root"))?;
+    let (keys, keys_base64) = split_encodings(&numbered("unseal")?)?;
+    let (recovery_keys, recovery_keys_base64) = split_encodings(&numbered("recovery")?)?;
+    if keys.is_empty() && recovery_keys.is_empty() {
+        anyhow::bail!("Input has no key entries: {key_prefix}-unseal-0 or {key_prefix}-recovery-0");
+    }
+
+    Ok(imported(PostInitResponse {
+        keys,
+        keys_base64,
+        recovery_keys,
+        recovery_keys_base64,
+        root_token,
+    }))
+}
+
+/// Wraps imported init data in an envelope. When it was created is unknown.
+fn imported(data: PostInitResponse) -> Envelope {
+    let mut envelope = Envelope::new(data);
+    envelope.metadata.created_at = None;
+    envelope
+}
+
+/// Reads entries from a JSON object of strings, or from a Kubernetes secret
+/// as printed by `kubectl get secret -o json`.
+fn entries_from_json(bytes: &[u8]) -> anyhow::Result<BTreeMap<String, Vec<u8>>> {
+    let value: serde_json::Value = serde_json::from_slice(bytes)?;
+    if value.get("kind").and_then(|kind| kind.as_str()) == Some("Secret") {
+        let secret: Secret = serde_json::from_value(value)?;
+        return Ok(secret
+            .data
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(key, value)| (key, value.0))
+            .collect());
+    }
+
+    let entries: BTreeMap<String, String> =
+        serde_json::from_value(value).context("Input is not a JSON object of strings")?;
+    Ok(entries
+        .into_iter()
+        .map(|(key, value)| (key, value.into_bytes()))
+        .collect())
+}
+
+/// Reads entries from a directory holding one file per entry, such as a
+/// mounted secret.
+fn read_dir(path: &Path) -> anyhow::Result<BTreeMap<String, Vec<u8>>> {
+    let mut entries = BTreeMap::new();
+    for entry in std::fs::read_dir(path)
+        .with_context(|| format!("Failed reading input directory: {}", path.display()))?
+    {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        entries.insert(name, std::fs::read(entry.path())?);
+    }
+    Ok(entries)
+}
+
+async fn read_kube_secret(
+    name: &str,
+    namespace: Option<&str>,
+) -> anyhow::Result<BTreeMap<String, Vec<u8>>> {
+    let client = kube::Client::try_default().await?;
+    let secrets: kube::Api<Secret> = match namespace {
+        Some(ns) => kube::Api::namespaced(client, ns),
+        None => kube::Api::default_namespaced(client),
+    };
+    let secret = secrets.get(name).await?;
+    Ok(secret
+        .data
+        .context("Kubernetes secret contained no data")?
+        .into_iter()
+        .map(|(key, value)| (key, value.0))
+        .collect())
+}
+
+/// Fills in whichever of the hex and base64 encodings of keys is missing.
+fn normalize(hex: &[String], base64: &[String]) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+    match (hex.is_empty(), base64.is_empty()) {
+        (true, true) => Ok((Vec::new(), Vec::new())),
+        (false, true) => encodings(hex, |key| {
+            Ok(data_encoding::HEXLOWER_PERMISSIVE.decode(key.as_bytes())?)
+        }),
+        (true, false) => encodings(base64, |key| {
+            Ok(data_encoding::BASE64.decode(key.as_bytes())?)
+        }),
+        (false, false) if hex.len() == base64.len() => Ok((hex.to_vec(), base64.to_vec())),
+        (false, false) => anyhow::bail!(
+            "Input has {} hex keys, but {} base64 keys",
+            hex.len(),
+            base64.len()
+        ),
+    }
+}
+
+/// Hex and base64 encodings of keys given in either.
+fn split_encodings(keys: &[String]) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+    encodings(keys, |key| {
+        data_encoding::HEXLOWER_PERMISSIVE
+            .decode(key.as_bytes())
+            .or_else(|_| data_encoding::BASE64.decode(key.as_bytes()))
+            .context("Neither hex nor base64")
+    })
+}
+
+fn encodings(
+    keys: &[String],
+    decode: impl Fn(&str) -> anyhow::Result<Vec<u8>>,
+) -> anyhow::Result<(Vec<String>, Vec<String>)> {
+    let mut hex = Vec::new();
+    let mut base64 = Vec::new();
+    for (i, key) in keys.iter().enumerate() {
+        let bytes = decode(key).with_context(|| format!("Failed decoding key #{i}"))?;
+        hex.push(data_encoding::HEXLOWER.encode(&bytes));
+        base64.push(data_encoding::BASE64.encode(&bytes));
+    }
+    Ok((hex, base64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vault_cli_output() -> &'static str {
+        r#"{
+            "unseal_keys_b64": ["qg==", "uw==", "zA=="],
+            "unseal_keys_hex": [],
+            "unseal_shares": 3,
+            "unseal_threshold": 2,
+            "recovery_keys_b64": [],
+            "recovery_keys_hex": [],
+            "recovery_keys_shares": 0,
+            "recovery_keys_threshold": 0,
+            "root_token": "hvs.root"
+        }"#
+    }
+
+    fn seal_status(n: i64, t: i64) -> GetSealStatusResponse {
+        GetSealStatusResponse {
+            r#type: "shamir".to_owned(),
+            initialized: true,
+            n,
+            t,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_vault_cli_output() {
+        let envelope = parse(vault_cli_output().as_bytes(), ImportFormat::Auto, "vault").unwrap();
+        let data = envelope.data().unwrap();
+        assert_eq!(data.keys, ["aa", "bb", "cc"]);
+        assert_eq!(data.root_token, "hvs.root");
+        assert_eq!(envelope.metadata.secret_threshold, Some(2));
+    }
+
+    #[test]
+    fn accepts_matching_vault() {
+        let envelope = parse(vault_cli_output().as_bytes(), ImportFormat::Auto, "vault").unwrap();
+        check_matches(&envelope, &seal_status(3, 2)).unwrap();
+    }
+
+    #[test]
+    fn rejects_other_vault() {
+        let envelope = parse(vault_cli_output().as_bytes(), ImportFormat::Auto, "vault").unwrap();
+        assert!(check_matches(&envelope, &seal_status(5, 2)).is_err());
+        assert!(check_matches(&envelope, &seal_status(3, 3)).is_err());
+
+        let uninitialized = GetSealStatusResponse {
+            initialized: false,
+            ..seal_status(3, 2)
+        };
+        assert!(check_matches(&envelope, &uninitialized).is_err());
+    }
+
+    #[test]
+    fn checks_recovery_keys_of_auto_unseal() {
+        let envelope = parse(vault_cli_output().as_bytes(), ImportFormat::Auto, "vault").unwrap();
+        let auto_unseal = GetSealStatusResponse {
+            r#type: "awskms".to_owned(),
+            ..seal_status(3, 2)
+        };
+        assert!(check_matches(&envelope, &auto_unseal).is_err());
+    }
+}
@@ -0,0 +1,384 @@
+use chrono::DateTime;
+use chrono::Utc;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use super::timestamp;
+use crate::save::fingerprint;
+use crate::save::Envelope;
+use crate::save::Metadata;
+use crate::save::Payload;
+use crate::save::SaveMethods;
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum InventoryFormat {
+    Table,
+    Json,
+}
+
+/// What one save method holds, described by fingerprints only.
+#[derive(Serialize)]
+struct Entry {
+    save_method: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    contents: Option<Contents>,
+}
+
+#[derive(Serialize)]
+struct Contents {
+    schema_version: u32,
+    /// Whether the save method holds all of init data, a part of it, some key
+    /// shares, or the root token.
+    payload: &'static str,
+    signature: String,
+    /// Number of key shares returned by init, if only some are held.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_shares: Option<usize>,
+    /// The split, if a part is held.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    split: Option<SplitInfo>,
+    unseal_keys: Vec<Key>,
+    recovery_keys: Vec<Key>,
+    root_token: Option<String>,
+    metadata: Metadata,
+    /// Seconds since init data was created.
+    created_age: Option<i64>,
+    /// Seconds since the root token was last rotated.
+    rotated_age: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct SplitInfo {
+    threshold: u8,
+    parts: u8,
+}
+
+#[derive(Serialize)]
+struct Key {
+    index: usize,
+    fingerprint: String,
+}
+
+/// Prints what each save method holds, without ever printing secret values.
+pub async fn inventory(save_methods: &SaveMethods, format: InventoryFormat) -> anyhow::Result<()> {
+    let mut entries = Vec::new();
+    for (name, envelope) in save_methods.load_each().await {
+        let entry = match envelope {
+            Ok(envelope) => Entry {
+                save_method: name.to_owned(),
+                error: None,
+                contents: Some(contents(save_methods, &envelope).await),
+            },
+            Err(err) => Entry {
+                save_method: name.to_owned(),
+                error: Some(format!("{err:#}")),
+                contents: None,
+            },
+        };
+        entries.push(entry);
+    }
+
+    match format {
+        InventoryFormat::Json => println!("{}", serde_json::to_string_pretty(&entries)?),
+        InventoryFormat::Table => {
+            for entry in &entries {
+                print_table(entry);
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn contents(save_methods: &SaveMethods, envelope: &Envelope) -> Contents {
+    let signature = match (&envelope.signature, save_methods.integrity()) {
+        (None, _) => "unsigned".to_owned(),
+        (Some(signature), None) => format!("{}, not verified", signature.algorithm),
+        (Some(signature), Some(integrity)) => match integrity.verify(envelope).await {
+            Ok(()) => format!("{}, verified", signature.algorithm),
+            Err(err) => format!("{}, invalid: {err}", signature.algorithm),
+        },
+    };
+
+    let mut contents = Contents {
+        schema_version: envelope.version,
+        payload: "data",
+        signature,
+        total_shares: None,
+        split: None,
+        unseal_keys: Vec::new(),
+        recovery_keys: Vec::new(),
+        root_token: None,
+        metadata: envelope.metadata.clone(),
+        created_age: envelope.metadata.created_at.map(age),
+        rotated_age: envelope.metadata.rotated_at.map(age),
+    };
+
+    let (data, indexes) = match &envelope.payload {
+        Payload::Data(data) => (
+            data,
+            (0..data.keys.len().max(data.recovery_keys.len())).collect(),
+        ),
+        Payload::Shares(shares) => {
+            contents.payload = "shares";
+            contents.total_shares = Some(shares.total);
+            (&shares.data, shares.indexes.clone())
+        }
+        Payload::Part(part) => {
+            contents.payload = "part";
+            contents.split = Some(SplitInfo {
+                threshold: part.threshold,
+                parts: part.parts,
+            });
+            return contents;
+        }
+        Payload::RootToken(root_token) => {
+            contents.payload = "root_token";
+            contents.root_token = Some(fingerprint(root_token));
+            return contents;
+        }
+    };
+    let keys = |keys: &[String]| -> Vec<Key> {
+        indexes
+            .iter()
+            .zip(keys)
+            .map(|(&index, key)| Key {
+                index,
+                fingerprint: fingerprint(key),
+            })
+            .collect()
+    };
+    contents.unseal_keys = keys(&data.keys);
+    contents.recovery_keys = keys(&data.recovery_keys);
+    if !data.root_token.is_empty() {
+        contents.root_token = Some(fingerprint(&data.root_token));
+    }
+    contents
+}
+
+fn print_table(entry: &Entry) {
+    println!("{}:", entry.save_method);
+    if let Some(error) = &entry.error {
+        println!("  Error:          {error}");
+    }
+    let Some(contents) = &entry.contents else {
+        return;
+    };
+    let metadata = &contents.metadata;
+
+    let payload = match (contents.total_shares, &contents.split) {
+        (Some(total), _) => format!(
+            "{} of {total} key shares",
+            contents.unseal_keys.len().max(contents.recovery_keys.len())
+        ),
+        (_, Some(split)) => format!("part of a {} of {} split", split.threshold, split.parts),
+        _ if contents.payload == "root_token" => "root token only".to_owned(),
+        _ => "complete init data".to_owned(),
+    };
+    println!("  Schema version: {}", contents.schema_version);
+    println!("  Payload:        {payload}");
+    println!("  Signature:      {}", contents.signature);
+    println!(
+        "  Cluster:        {} ({})",
+        metadata.cluster_name.as_deref().unwrap_or("-"),
+        metadata.cluster_id.as_deref().unwrap_or("-")
+    );
+    println!(
+        "  Server pin:     {}",
+        metadata.server_pin_sha256.as_deref().unwrap_or("-")
+    );
+    println!(
+        "  Vault version:  {}",
+        metadata.vault_version.as_deref().unwrap_or("-")
+    );
+    println!(
+        "  Seal type:      {}",
+        metadata.seal_type.as_deref().unwrap_or("-")
+    );
+    println!(
+        "  Threshold:      {}",
+        match (metadata.secret_threshold, metadata.secret_shares) {
+            (Some(t), Some(n)) => format!("{t} of {n}"),
+            _ => "-".to_owned(),
+        }
+    );
+    println!(
+        "  Created:        {}",
+        timestamp_with_age(metadata.created_at, contents.created_age)
+    );
+    println!(
+        "  Rotated:        {}",
+        timestamp_with_age(metadata.rotated_at, contents.rotated_age)
+    );
+    println!(
+        "  Root token:     {}",
+        contents.root_token.as_deref().unwrap_or("-")
+    );
+
+    if contents.unseal_keys.is_empty() && contents.recovery_keys.is_empty() {
+        return;
+    }
+    println!("  {:<14} {:<6} FINGERPRINT", "KEY", "INDEX");
+    for (kind, keys) in [
+        ("unseal", &contents.unseal_keys),
+        ("recovery", &contents.recovery_keys),
+    ] {
+        for key in keys {
+            println!("  {kind:<14} {:<6} {}", key.index, key.fingerprint);
+        }
+    }
+}
+
+/// Seconds elapsed since the given time.
+fn age(time: DateTime<Utc>) -> i64 {
+    (Utc::now() - time).num_seconds()
+}
+
+/// A time as shown in tables, followed by how long ago it was.
+fn timestamp_with_age(time: Option<DateTime<Utc>>, age: Option<i64>) -> String {
+    let (Some(_), Some(age)) = (time, age) else {
+        return "-".to_owned();
+    };
+    let age = match age {
+        a if a >= 86_400 => format!("{}d", a / 86_400),
+        a if a >= 3_600 => format!("{}h", a / 3_600),
+        a => format!("{}m", a / 60),
+    };
+    format!("{} ({age} ago)", timestamp(time))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use serde_json::json;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::save::Registry;
+    use crate::vault::models::sys::init::PostInitResponse;
+
+    fn save_methods() -> SaveMethods {
+        let config: Config = hcl::from_str(
+            "save_method \"file\" {\n  type = \"file\"\n  path = \"/nonexistent/init.json\"\n}\n",
+        )
+        .unwrap();
+        Registry::default().build(&config).unwrap()
+    }
+
+    fn init_data() -> Envelope {
+        Envelope::new(PostInitResponse {
+            keys: vec!["a".to_owned(), "b".to_owned(), "c".to_owned()],
+            keys_base64: vec!["YQ==".to_owned(), "Yg==".to_owned(), "Yw==".to_owned()],
+            root_token: "hvs.root".to_owned(),
+            ..Default::default()
+        })
+    }
+
+    /// `init_data` with its payload replaced, as a save method would hold it.
+    fn with_payload(key: &str, payload: serde_json::Value) -> Envelope {
+        let mut value = serde_json::to_value(init_data()).unwrap();
+        let object = value.as_object_mut().unwrap();
+        object.remove("data");
+        object.insert(key.to_owned(), payload);
+        Envelope::from_slice(&serde_json::to_vec(&value).unwrap()).unwrap()
+    }
+
+    fn fingerprints(keys: &[Key]) -> Vec<(usize, &str)> {
+        keys.iter()
+            .map(|key| (key.index, key.fingerprint.as_str()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn describes_complete_init_data() {
+        let contents = contents(&save_methods(), &init_data()).await;
+        assert_eq!(contents.payload, "data");
+        assert_eq!(contents.signature, "unsigned");
+        assert_eq!(
+            fingerprints(&contents.unseal_keys),
+            [
+                (0, fingerprint("a").as_str()),
+                (1, fingerprint("b").as_str()),
+                (2, fingerprint("c").as_str())
+            ]
+        );
+        assert!(contents.recovery_keys.is_empty());
+        assert_eq!(contents.root_token, Some(fingerprint("hvs.root")));
+        assert!(contents.created_age.is_some());
+        assert_eq!(contents.rotated_age, None);
+    }
+
+    #[tokio::test]
+    async fn describes_key_shares_by_their_indexes() {
+        let envelope = with_payload(
+            "shares",
+            json!({
+                "indexes": [0, 2],
+                "total": 3,
+                "data": {"keys": ["a", "c"], "keys_base64": ["YQ==", "Yw=="], "root_token": ""},
+            }),
+        );
+        let contents = contents(&save_methods(), &envelope).await;
+        assert_eq!(contents.payload, "shares");
+        assert_eq!(contents.total_shares, Some(3));
+        assert_eq!(
+            fingerprints(&contents.unseal_keys),
+            [
+                (0, fingerprint("a").as_str()),
+                (2, fingerprint("c").as_str())
+            ]
+        );
+        assert_eq!(contents.root_token, None);
+    }
+
+    #[tokio::test]
+    async fn describes_part() {
+        let envelope = with_payload(
+            "part",
+            json!({"threshold": 2, "parts": 3, "digest": "", "salt": "", "share": ""}),
+        );
+        let contents = contents(&save_methods(), &envelope).await;
+        assert_eq!(contents.payload, "part");
+        let split = contents.split.unwrap();
+        assert_eq!((split.threshold, split.parts), (2, 3));
+        assert!(contents.unseal_keys.is_empty());
+        assert_eq!(contents.root_token, None);
+    }
+
+    #[tokio::test]
+    async fn describes_root_token() {
+        let envelope = with_payload("root_token", json!("hvs.root"));
+        let contents = contents(&save_methods(), &envelope).await;
+        assert_eq!(contents.payload, "root_token");
+        assert!(contents.unseal_keys.is_empty());
+        assert_eq!(contents.root_token, Some(fingerprint("hvs.root")));
+    }
+
+    #[tokio::test]
+    async fn notes_signature_is_not_verified_without_integrity() {
+        let mut envelope = init_data();
+        envelope.signature =
+            Some(serde_json::from_value(json!({"algorithm": "ed25519", "value": ""})).unwrap());
+        let contents = contents(&save_methods(), &envelope).await;
+        assert_eq!(contents.signature, "ed25519, not verified");
+    }
+
+    #[test]
+    fn formats_timestamp_with_age() {
+        let time = "2024-01-02T03:04:05Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(
+            timestamp_with_age(Some(time), Some(Duration::days(3).num_seconds() + 5)),
+            "2024-01-02T03:04:05Z (3d ago)"
+        );
+        assert_eq!(
+            timestamp_with_age(Some(time), Some(Duration::hours(5).num_seconds())),
+            "2024-01-02T03:04:05Z (5h ago)"
+        );
+        assert_eq!(
+            timestamp_with_age(Some(time), Some(125)),
+            "2024-01-02T03:04:05Z (2m ago)"
+        );
+        assert_eq!(timestamp_with_age(None, None), "-");
+    }
+}
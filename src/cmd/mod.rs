@@ -0,0 +1,16 @@
+use chrono::DateTime;
+use chrono::SecondsFormat;
+use chrono::Utc;
+
+pub mod import;
+pub mod inventory;
+pub mod paper;
+pub mod status;
+pub mod versions;
+
+/// A time as shown in tables, or `-` if unknown.
+fn timestamp(time: Option<DateTime<Utc>>) -> String {
+    time.map_or("-".to_owned(), |t| {
+        t.to_rfc3339_opts(SecondsFormat::Secs, true)
+    })
+}
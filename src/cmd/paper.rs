@@ -0,0 +1,120 @@
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use anyhow::Context;
+use clap::ValueEnum;
+use tracing::error;
+use tracing::info;
+
+use crate::paper;
+use crate::paper::mnemonic;
+use crate::save::SaveMethods;
+use crate::vault::models::sys::unseal::PostUnsealRequest;
+use crate::vault::VaultClient;
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum PaperFormat {
+    Text,
+    Pdf,
+}
+
+/// Renders each key share on its own page for printing.
+pub async fn export_paper(
+    save_methods: &SaveMethods,
+    format: PaperFormat,
+    output: Option<&Path>,
+    recovery: bool,
+) -> anyhow::Result<()> {
+    let phase = "export_paper";
+
+    info!(phase, "Reading init data from save methods");
+    let envelope = save_methods.load_init_all().await.inspect_err(|_| {
+        error!(phase, "Failed reading init data from save methods");
+    })?;
+
+    let pages = paper::pages(&envelope, recovery)?;
+    let contents = match format {
+        PaperFormat::Text => paper::render_text(&pages).into_bytes(),
+        PaperFormat::Pdf => paper::pdf::render(&pages),
+    };
+
+    match output {
+        Some(path) => {
+            let mut file = std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(path)
+                .with_context(|| format!("Failed opening output file: {}", path.display()))?;
+            file.write_all(&contents)?;
+            info!(
+                phase,
+                pages = pages.len(),
+                path = &path.to_string_lossy().to_string(),
+                "Wrote paper backup"
+            );
+        }
+        None if matches!(format, PaperFormat::Pdf) => {
+            anyhow::bail!("An output file is required for PDF");
+        }
+        None => std::io::stdout().write_all(&contents)?,
+    }
+
+    Ok(())
+}
+
+/// Reads shares typed from paper backups, one per line, and submits them to
+/// unseal Vault. Word numbering copied from the page is ignored.
+pub async fn import_paper(vault: &VaultClient, input: Option<&Path>) -> anyhow::Result<()> {
+    let phase = "import_paper";
+
+    let text = match input {
+        Some(path) => std::fs::read_to_string(path)
+            .with_context(|| format!("Failed reading input file: {}", path.display()))?,
+        None => std::io::read_to_string(std::io::stdin())?,
+    };
+
+    let mut shares = Vec::new();
+    for (line_number, line) in text.lines().enumerate() {
+        let words: Vec<&str> = line
+            .split_whitespace()
+            .filter(|word| word.trim_end_matches('.').parse::<usize>().is_err())
+            .collect();
+        if words.is_empty() {
+            continue;
+        }
+        let (index, share) = mnemonic::decode(&words)
+            .with_context(|| format!("Failed decoding share on line {}", line_number + 1))?;
+        info!(phase, share = index + 1, "Decoded share");
+        shares.push((index, share));
+    }
+    if shares.is_empty() {
+        anyhow::bail!("No shares were provided");
+    }
+
+    let seal_status = vault.get_seal_status().await?;
+    if !seal_status.sealed {
+        info!(phase, "Vault is already unsealed");
+        return Ok(());
+    }
+
+    for (index, share) in shares {
+        info!(phase, "Submitting share #{}", index + 1);
+        let unseal_request = PostUnsealRequest {
+            key: Some(data_encoding::HEXLOWER.encode(&share)),
+            reset: false,
+            migrate: false,
+        };
+        let unseal_response = vault.submit_unseal_key(&unseal_request).await?;
+        if !unseal_response.sealed {
+            info!(phase, "Successfully unsealed Vault");
+            return Ok(());
+        }
+    }
+
+    Err(anyhow::anyhow!(
+        "Shares were accepted, but not enough were provided to unseal Vault"
+    ))
+}
@@ -0,0 +1,406 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use tracing::debug;
+use tracing::error;
+
+use crate::pgp::Decrypter;
+use crate::save::fingerprint;
+use crate::save::SaveMethods;
+use crate::vault;
+use crate::vault::models::auth::token::LookupSelfData;
+use crate::vault::models::sys::ha_status::HaNode;
+use crate::vault::models::sys::health::GetHealthRequest;
+use crate::vault::Feature;
+use crate::vault::Flavor;
+use crate::vault::Server;
+use crate::vault::VaultClient;
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum StatusFormat {
+    Table,
+    Json,
+}
+
+/// State of Vault, and of the root token held by the save methods.
+#[derive(Serialize)]
+struct Status {
+    address: String,
+    initialized: bool,
+    sealed: bool,
+    seal_type: String,
+    flavor: Flavor,
+    version: String,
+    cluster_name: Option<String>,
+    cluster_id: Option<String>,
+    standby: bool,
+    /// Whether Vault is a DR secondary, which is neither active nor a standby
+    /// of its own cluster.
+    dr_secondary: bool,
+    /// Address of the active node, if Vault is a standby.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    active_node: Option<String>,
+    root_token: RootToken,
+    /// Nodes of the cluster, if Vault is the active node and the root token
+    /// is valid.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    nodes: Vec<HaNode>,
+}
+
+#[derive(Serialize)]
+struct RootToken {
+    /// Whether the token is `valid`, `invalid` (revoked or expired),
+    /// `encrypted` with PGP and not decrypted, `unavailable` from the save
+    /// methods, or `unknown` as it could not be looked up.
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    lookup: Option<LookupSelfData>,
+}
+
+/// Prints the state of Vault, and whether the root token held by the save
+/// methods is still valid, without printing secret values.
+pub async fn status(
+    vault: &VaultClient,
+    address: &url::Url,
+    save_methods: &SaveMethods,
+    decrypter: &Decrypter,
+    format: StatusFormat,
+) -> anyhow::Result<()> {
+    let phase = "status";
+
+    let health = vault
+        .get_health(&GetHealthRequest::any_state())
+        .await
+        .inspect_err(|_| {
+            error!(phase, "Failed checking health");
+        })?;
+    let seal_status = vault.get_seal_status().await.inspect_err(|_| {
+        error!(phase, "Failed checking seal status");
+    })?;
+    let mut server = Server::detect(&seal_status);
+    let standby = health.standby || health.performance_standby;
+    let dr_secondary = health.is_dr_secondary();
+    let active_node = if standby {
+        vault
+            .get_leader()
+            .await
+            .ok()
+            .map(|leader| leader.leader_address)
+            .filter(|address| !address.is_empty())
+    } else {
+        None
+    };
+
+    let (root_token, token) = root_token(vault, save_methods, decrypter).await;
+    if let Some(token) = &token {
+        match vault
+            .with_token(token.clone().into())
+            .get_version_history()
+            .await
+        {
+            Ok(history) => server = server.with_history(&history.data),
+            Err(err) => debug!(phase, %err, "Failed reading version history"),
+        }
+    }
+    let mut nodes = Vec::new();
+    if let (Some(token), false, false, true) = (
+        token,
+        standby,
+        dr_secondary,
+        server.supports(Feature::HaStatus),
+    ) {
+        match vault.with_token(token.into()).get_ha_status().await {
+            Ok(ha_status) => nodes = ha_status.nodes,
+            Err(err) => debug!(phase, %err, "Failed reading HA status"),
+        }
+    }
+
+    let status = Status {
+        address: address.to_string(),
+        initialized: health.initialized,
+        sealed: health.sealed,
+        seal_type: seal_status.r#type,
+        flavor: server.flavor,
+        version: seal_status.version,
+        cluster_name: health.cluster_name,
+        cluster_id: health.cluster_id,
+        standby,
+        dr_secondary,
+        active_node,
+        root_token,
+        nodes,
+    };
+    match format {
+        StatusFormat::Json => println!("{}", serde_json::to_string_pretty(&status)?),
+        StatusFormat::Table => print_table(&status),
+    }
+    Ok(())
+}
+
+/// Status of the root token held by the save methods, and the token itself if
+/// it is valid.
+async fn root_token(
+    vault: &VaultClient,
+    save_methods: &SaveMethods,
+    decrypter: &Decrypter,
+) -> (RootToken, Option<String>) {
+    let mut status = RootToken {
+        status: "unavailable",
+        fingerprint: None,
+        error: None,
+        lookup: None,
+    };
+
+    let stored = match save_methods.load_root_token().await {
+        Ok(envelope) => envelope.root_token().map(ToOwned::to_owned),
+        Err(err) => Err(err),
+    };
+    let stored = match stored {
+        Ok(stored) => stored,
+        Err(err) => {
+            status.error = Some(format!("{err:#}"));
+            return (status, None);
+        }
+    };
+    status.fingerprint = Some(fingerprint(&stored));
+    let token = match decrypter.root_token(&stored) {
+        Ok(Some(token)) => token,
+        Ok(None) => {
+            status.status = "encrypted";
+            return (status, None);
+        }
+        Err(err) => {
+            status.error = Some(format!("{err:#}"));
+            return (status, None);
+        }
+    };
+
+    match vault
+        .with_token(token.clone().into())
+        .get_auth_token_lookup_self()
+        .await
+    {
+        Ok(lookup) => {
+            status.status = "valid";
+            status.lookup = Some(lookup.data);
+            (status, Some(token))
+        }
+        Err(vault::Error::PermissionDenied { .. }) => {
+            status.status = "invalid";
+            (status, None)
+        }
+        Err(err) => {
+            status.status = "unknown";
+            status.error = Some(err.to_string());
+            (status, None)
+        }
+    }
+}
+
+fn print_table(status: &Status) {
+    println!("Vault:            {}", status.address);
+    println!("  Initialized:    {}", status.initialized);
+    println!("  Sealed:         {}", status.sealed);
+    println!("  Seal type:      {}", status.seal_type);
+    println!("  Server:         {} {}", status.flavor, status.version);
+    println!(
+        "  Cluster:        {} ({})",
+        status.cluster_name.as_deref().unwrap_or("-"),
+        status.cluster_id.as_deref().unwrap_or("-")
+    );
+    let ha_mode = if status.dr_secondary {
+        "DR secondary"
+    } else if status.standby {
+        "standby"
+    } else {
+        "active"
+    };
+    println!("  HA mode:        {ha_mode}");
+    if let Some(active_node) = &status.active_node {
+        println!("  Active node:    {active_node}");
+    }
+
+    let root_token = &status.root_token;
+    println!("Root token:");
+    println!("  Status:         {}", root_token.status);
+    println!(
+        "  Fingerprint:    {}",
+        root_token.fingerprint.as_deref().unwrap_or("-")
+    );
+    if let Some(error) = &root_token.error {
+        println!("  Error:          {error}");
+    }
+    if let Some(lookup) = &root_token.lookup {
+        println!("  Policies:       {}", lookup.policies.join(", "));
+        println!(
+            "  Expires:        {}",
+            lookup.expire_time.as_deref().unwrap_or("never")
+        );
+    }
+
+    if status.nodes.is_empty() {
+        return;
+    }
+    println!("Nodes:");
+    println!(
+        "  {:<24} {:<40} {:<8} VERSION",
+        "HOSTNAME", "ADDRESS", "ACTIVE"
+    );
+    for node in &status.nodes {
+        println!(
+            "  {:<24} {:<40} {:<8} {}",
+            node.hostname, node.api_address, node.active_node, node.version
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::pgp::key_pair;
+    use crate::save::Registry;
+    use crate::vault::Call;
+    use crate::vault::Headers;
+    use crate::vault::Middleware;
+    use crate::vault::Next;
+    use crate::vault::Proxy;
+    use crate::vault::Retry;
+    use crate::vault::Tls;
+
+    const ROOT_TOKEN: Option<&str> = Some(r#"{"root_token":"hvs.root"}"#);
+
+    const LOOKUP_SELF: &str = r#"{"data":{"accessor":"abc","policies":["root"],"ttl":0}}"#;
+
+    /// Responds to every request with `status` and `body` instead of sending
+    /// it.
+    struct Stub {
+        status: u16,
+        body: &'static str,
+        requests: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl Middleware for Stub {
+        async fn handle(
+            &self,
+            _client: &VaultClient,
+            _call: Call,
+            _next: Next<'_>,
+        ) -> Result<reqwest::Response, vault::Error> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let response = hyper::Response::builder()
+                .status(self.status)
+                .body(self.body)
+                .unwrap();
+            vault::Error::check(response.into()).await
+        }
+    }
+
+    /// File save method holding `contents`, if any.
+    fn save_methods(name: &str, contents: Option<&str>) -> SaveMethods {
+        let path = std::env::temp_dir().join(format!(
+            "vault-init-status-{}-{name}.json",
+            std::process::id()
+        ));
+        match contents {
+            Some(contents) => std::fs::write(&path, contents).unwrap(),
+            None => {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+        let config: Config = hcl::from_str(&format!(
+            "save_method \"file\" {{\n  type = \"file\"\n  path = \"{}\"\n}}\n",
+            path.display()
+        ))
+        .unwrap();
+        Registry::default().build(&config).unwrap()
+    }
+
+    /// Status of the root token held in `contents`, with Vault responding to
+    /// lookups with `status` and `body`, and the number of requests made.
+    async fn root_token_status(
+        name: &str,
+        contents: Option<&str>,
+        status: u16,
+        body: &'static str,
+    ) -> (RootToken, Option<String>, usize) {
+        let stub = Arc::new(Stub {
+            status,
+            body,
+            requests: AtomicUsize::new(0),
+        });
+        let vault = VaultClient::new(
+            url::Url::parse("http://127.0.0.1:8200").unwrap(),
+            &Tls::default(),
+            &Retry::default(),
+            &Headers::default(),
+            &Proxy::default(),
+        )
+        .unwrap()
+        .with_middleware(stub.clone());
+        let save_methods = save_methods(name, contents);
+        let (status, token) = root_token(&vault, &save_methods, &Decrypter::default()).await;
+        (status, token, stub.requests.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn reports_valid_root_token() {
+        let (status, token, requests) =
+            root_token_status("valid", ROOT_TOKEN, 200, LOOKUP_SELF).await;
+        assert_eq!(status.status, "valid");
+        assert_eq!(status.fingerprint, Some(fingerprint("hvs.root")));
+        assert_eq!(status.lookup.unwrap().accessor, "abc");
+        assert_eq!(token.as_deref(), Some("hvs.root"));
+        assert_eq!(requests, 1);
+    }
+
+    #[tokio::test]
+    async fn reports_invalid_root_token() {
+        let body = r#"{"errors":["permission denied"]}"#;
+        let (status, token, _) = root_token_status("invalid", ROOT_TOKEN, 403, body).await;
+        assert_eq!(status.status, "invalid");
+        assert_eq!(status.error, None);
+        assert_eq!(token, None);
+    }
+
+    #[tokio::test]
+    async fn reports_unknown_if_lookup_fails() {
+        let body = r#"{"errors":["internal error"]}"#;
+        let (status, token, _) = root_token_status("unknown", ROOT_TOKEN, 500, body).await;
+        assert_eq!(status.status, "unknown");
+        assert!(status.error.unwrap().contains("internal error"));
+        assert_eq!(token, None);
+    }
+
+    #[tokio::test]
+    async fn reports_encrypted_root_token() {
+        let (_, public_key) = key_pair("");
+        let encrypted = crate::pgp::encrypt(&public_key, "hvs.root").unwrap();
+        let contents = format!(r#"{{"root_token":"{encrypted}"}}"#);
+        let (status, token, requests) =
+            root_token_status("encrypted", Some(&contents), 200, LOOKUP_SELF).await;
+        assert_eq!(status.status, "encrypted");
+        assert_eq!(status.fingerprint, Some(fingerprint(&encrypted)));
+        assert_eq!(token, None);
+        assert_eq!(requests, 0);
+    }
+
+    #[tokio::test]
+    async fn reports_unavailable_root_token() {
+        let (status, token, requests) =
+            root_token_status("unavailable", None, 200, LOOKUP_SELF).await;
+        assert_eq!(status.status, "unavailable");
+        assert_eq!(status.fingerprint, None);
+        assert!(status.error.is_some());
+        assert_eq!(token, None);
+        assert_eq!(requests, 0);
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::bail;
+use tracing::error;
+use tracing::info;
+
+use super::timestamp;
+use crate::save::fingerprint;
+use crate::save::Payload;
+use crate::save::SaveMethods;
+
+/// Prints the versions of init data retained by each save method, identified
+/// by metadata and fingerprints only. Fails if the named save method does not
+/// retain versions, or if listing any of them failed.
+pub async fn list_versions(
+    save_methods: &SaveMethods,
+    save_method: Option<&str>,
+) -> anyhow::Result<()> {
+    let phase = "list_versions";
+
+    let selected: Vec<_> = match save_method {
+        Some(name) => vec![(name, save_methods.versioned_by_name(name)?)],
+        None => save_methods.versioned().collect(),
+    };
+    if selected.is_empty() {
+        let msg = "No save method retains versions, set `history` to retain them";
+        error!(phase, msg);
+        bail!(msg);
+    }
+
+    let mut failed = Vec::new();
+    for (name, versioned) in selected {
+        let versions = match versioned.list_versions().await {
+            Ok(versions) => versions,
+            Err(err) => {
+                error!(phase, save_method = name, ?err, "Failed listing versions");
+                failed.push(name);
+                continue;
+            }
+        };
+
+        println!("{name}:");
+        println!(
+            "  {:<8} {:<20} {:<20} {:<36} ROOT TOKEN",
+            "VERSION", "CREATED", "ROTATED", "CLUSTER ID"
+        );
+        for version in versions {
+            let metadata = &version.envelope.metadata;
+            println!(
+                "  {:<8} {:<20} {:<20} {:<36} {}",
+                version.version,
+                timestamp(metadata.created_at),
+                timestamp(metadata.rotated_at),
+                metadata.cluster_id.as_deref().unwrap_or("-"),
+                match &version.envelope.payload {
+                    Payload::Data(data) => fingerprint(&data.root_token),
+                    Payload::Part(part) => format!("(part of {})", part.parts),
+                    Payload::Shares(shares) if shares.data.root_token.is_empty() => {
+                        "-".to_owned()
+                    }
+                    Payload::Shares(shares) => fingerprint(&shares.data.root_token),
+                    Payload::RootToken(root_token) => fingerprint(root_token),
+                },
+            );
+        }
+    }
+
+    if !failed.is_empty() {
+        bail!(
+            "Failed listing versions of save methods: {}",
+            failed.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Makes a retained version current again in the given save method.
+pub async fn restore_version(
+    save_methods: &SaveMethods,
+    save_method: &str,
+    version: usize,
+) -> anyhow::Result<()> {
+    let phase = "restore_version";
+
+    info!(phase, save_method, version, "Restoring init data version");
+    save_methods
+        .restore_version(save_method, version)
+        .await
+        .inspect_err(|_| {
+            error!(
+                phase,
+                save_method, version, "Failed restoring init data version"
+            );
+        })?;
+    info!(
+        phase,
+        save_method, version, "Successfully restored init data version"
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::save::Registry;
+
+    /// Empty directory for the files of one test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("vault-init-versions-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A `versioned` file save method retaining versions, and a `plain` one
+    /// that does not.
+    fn save_methods(dir: &Path) -> SaveMethods {
+        let config = format!(
+            "save_method \"versioned\" {{\n  type = \"file\"\n  path = \"{}\"\n  history = 2\n}}\n\
+             save_method \"plain\" {{\n  type = \"file\"\n  path = \"{}\"\n}}\n",
+            dir.join("versioned.json").display(),
+            dir.join("plain.json").display(),
+        );
+        let config: Config = hcl::from_str(&config).unwrap();
+        Registry::default().build(&config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn lists_versions_of_named_save_method() {
+        let dir = test_dir("list");
+        let save_methods = save_methods(&dir);
+        std::fs::write(dir.join("versioned.json"), r#"{"root_token":"hvs.root"}"#).unwrap();
+
+        list_versions(&save_methods, None).await.unwrap();
+        list_versions(&save_methods, Some("versioned"))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn fails_for_save_method_without_versions() {
+        let dir = test_dir("unversioned");
+        let save_methods = save_methods(&dir);
+
+        assert!(list_versions(&save_methods, Some("plain")).await.is_err());
+        assert!(list_versions(&save_methods, Some("missing")).await.is_err());
+        assert!(restore_version(&save_methods, "plain", 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn fails_when_listing_fails() {
+        let dir = test_dir("unreadable");
+        let save_methods = save_methods(&dir);
+        std::fs::write(dir.join("versioned.json"), "not json").unwrap();
+
+        assert!(list_versions(&save_methods, None).await.is_err());
+    }
+}
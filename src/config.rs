@@ -1,49 +1,116 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use k8s_openapi::api::core::v1::Secret;
 use serde::Deserialize;
 use serde::Serialize;
 
-use crate::save::File;
-use crate::save::KubeSecret;
-use crate::save::Load;
-use crate::save::Save;
-use crate::vault::models::sys::init::PostInitResponse;
+use crate::emergency::Emergency;
+use crate::identity::Identity;
+use crate::journal::Journal;
+use crate::pgp::Pgp;
+use crate::save::Integrity;
+use crate::save::Split;
+use crate::vault::Headers;
+use crate::vault::Proxy;
+use crate::vault::Retry;
+use crate::vault::Tls;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
-    pub save_method: SaveMethod,
+    /// Save method blocks by name, built into save methods by the
+    /// [`Registry`](crate::save::Registry).
+    pub save_method: hcl::Map<String, hcl::Value>,
+    pub integrity: Option<Integrity>,
+    pub split: Option<Split>,
+    pub pgp: Option<Pgp>,
+    pub emergency: Option<Emergency>,
+    pub journal: Option<Journal>,
+    pub tls: Option<Tls>,
+    pub identity: Option<Identity>,
+    pub retry: Option<Retry>,
+    pub headers: Option<Headers>,
+    pub proxy: Option<Proxy>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SaveMethod {
-    pub file: Option<File>,
-    pub kube_secret: Option<KubeSecret>,
+/// Location of key material, read from `file`, `env` or `secret`, whichever is
+/// set first in that order. Leading and trailing whitespace is ignored, unless
+/// the key may be binary.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct KeySource {
+    pub file: Option<PathBuf>,
+    pub env: Option<String>,
+    pub secret: Option<SecretRef>,
 }
 
-impl SaveMethod {
-    pub async fn save_init_all(&self, data: &PostInitResponse) -> anyhow::Result<()> {
-        if let Some(file) = self.file.clone() {
-            file.save_init(data).await?;
-        }
-        if let Some(kube_secret) = self.kube_secret.clone() {
-            kube_secret.save_init(data).await?;
-        }
+/// A key within a Kubernetes secret.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct SecretRef {
+    pub name: String,
+    pub namespace: Option<String>,
+    pub key: String,
+}
 
-        Ok(())
+impl KeySource {
+    pub async fn read(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(self.read_binary().await?.trim_ascii().to_vec())
     }
 
-    pub async fn load_init_all(&self) -> anyhow::Result<PostInitResponse> {
-        if let Some(file) = self.file.clone() {
-            if let Ok(data) = file.load_init().await {
-                return Ok(data);
-            }
-        }
-        if let Some(kube_secret) = self.kube_secret.clone() {
-            if let Ok(data) = kube_secret.load_init().await {
-                return Ok(data);
-            }
-        }
-
-        Err(anyhow::anyhow!(
-            "Failed loading init data from all save methods"
-        ))
+    /// Reads the key as is, as binary keys may start or end with bytes that
+    /// are whitespace.
+    pub async fn read_binary(&self) -> anyhow::Result<Vec<u8>> {
+        let contents = if let Some(file) = &self.file {
+            tokio::fs::read(file)
+                .await
+                .with_context(|| format!("Failed reading key file: {}", file.display()))?
+        } else if let Some(env) = &self.env {
+            std::env::var(env)
+                .with_context(|| format!("Failed reading key from environment: {env}"))?
+                .into_bytes()
+        } else if let Some(secret) = &self.secret {
+            secret.read().await.with_context(|| {
+                format!(
+                    "Failed reading key from Kubernetes secret: {}/{}",
+                    secret.name, secret.key
+                )
+            })?
+        } else {
+            anyhow::bail!("Key source has none of file, env or secret set");
+        };
+        Ok(contents)
+    }
+}
+
+impl SecretRef {
+    async fn read(&self) -> anyhow::Result<Vec<u8>> {
+        let client = kube::Client::try_default().await?;
+        let secrets: kube::Api<Secret> = match &self.namespace {
+            Some(ns) => kube::Api::namespaced(client, ns),
+            None => kube::Api::default_namespaced(client),
+        };
+        let secret = secrets.get(&self.name).await?;
+        let value = secret
+            .data
+            .and_then(|mut data| data.remove(&self.key))
+            .context("Kubernetes secret did not contain expected key")?;
+        Ok(value.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn trims_whitespace_unless_binary() {
+        let path =
+            std::env::temp_dir().join(format!("vault-init-config-{}-key", std::process::id()));
+        std::fs::write(&path, b" \x99key\x0a").unwrap();
+        let source = KeySource {
+            file: Some(path),
+            ..Default::default()
+        };
+        assert_eq!(source.read().await.unwrap(), b"\x99key");
+        assert_eq!(source.read_binary().await.unwrap(), b" \x99key\x0a");
     }
 }
@@ -0,0 +1,231 @@
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use pgp::Deserializable;
+use pgp::SignedPublicKey;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::error;
+use tracing::warn;
+
+use crate::config::KeySource;
+use crate::save::Envelope;
+
+/// Exit code when init data could only be written to the emergency fallback.
+pub const EXIT_CODE: i32 = 3;
+
+/// Where init data goes if too many save methods fail to read it back after
+/// Vault is initialized, so that the keys are not lost with the process. Init
+/// is refused unless this is configured or explicitly disabled.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Emergency {
+    /// PGP public key to encrypt init data to, ASCII-armored or binary.
+    pub public_key: Option<KeySource>,
+    /// File to write encrypted init data to. Written to stderr if not set, or
+    /// if the file cannot be written.
+    pub path: Option<PathBuf>,
+    pub disabled: Option<bool>,
+}
+
+/// The emergency fallback, with its public key read and ready to encrypt to.
+pub struct Sink {
+    public_key: SignedPublicKey,
+    path: Option<PathBuf>,
+}
+
+/// Init data was not saved, but was written to the emergency fallback.
+#[derive(Debug)]
+pub struct Saved {
+    pub location: String,
+}
+
+impl std::fmt::Display for Saved {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Init data could not be saved, and was written encrypted to the emergency fallback \
+             at {}",
+            self.location
+        )
+    }
+}
+
+impl std::error::Error for Saved {}
+
+impl Emergency {
+    /// Reads the public key, so that problems surface before Vault is
+    /// initialized. `None` if the fallback is disabled.
+    pub async fn sink(&self) -> anyhow::Result<Option<Sink>> {
+        if self.disabled.unwrap_or(false) {
+            warn!("Emergency fallback is disabled, keys are lost if saving fails");
+            return Ok(None);
+        }
+        let Some(public_key) = &self.public_key else {
+            anyhow::bail!(
+                "No emergency fallback is configured for init data, set `emergency {{ public_key \
+                 {{ ... }} }}` or `emergency {{ disabled = true }}`"
+            );
+        };
+
+        let bytes = public_key.read_binary().await?;
+        let public_key = if bytes.trim_ascii_start().starts_with(b"-----BEGIN") {
+            SignedPublicKey::from_string(std::str::from_utf8(bytes.trim_ascii())?)?.0
+        } else {
+            SignedPublicKey::from_bytes(bytes.as_slice())?
+        };
+        let sink = Sink {
+            public_key,
+            path: self.path.clone(),
+        };
+        // Fail now rather than when the keys depend on it
+        sink.encrypt(b"")?;
+        Ok(Some(sink))
+    }
+}
+
+impl Sink {
+    /// Writes init data encrypted to the configured path, or stderr if that
+    /// fails, returning where it was written.
+    pub fn write(&self, envelope: &Envelope) -> anyhow::Result<String> {
+        let armored = self.encrypt(&serde_json::to_vec(envelope)?)?;
+
+        if let Some(path) = &self.path {
+            let written = std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(path)
+                .and_then(|mut file| {
+                    file.write_all(armored.as_bytes())?;
+                    file.sync_all()
+                });
+            match written {
+                Ok(()) => return Ok(path.display().to_string()),
+                Err(err) => error!(
+                    ?err,
+                    ?path,
+                    "Failed writing to emergency fallback path, writing to stderr"
+                ),
+            }
+        }
+
+        eprintln!("{armored}");
+        Ok("stderr".to_owned())
+    }
+
+    /// Encrypts to the public key, ASCII-armored.
+    fn encrypt(&self, data: &[u8]) -> anyhow::Result<String> {
+        let encrypted = crate::pgp::encrypt_to(&self.public_key, data)
+            .context("Failed encrypting to emergency fallback public key")?;
+        Ok(encrypted.to_armored_string(None.into())?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    use pgp::Message;
+    use pgp::SignedSecretKey;
+
+    use super::*;
+    use crate::pgp::key_pair;
+    use crate::vault::models::sys::init::PostInitResponse;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "vault-init-emergency-{}-{name}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn init_data() -> Envelope {
+        Envelope::new(PostInitResponse {
+            keys: vec!["aa".to_owned()],
+            keys_base64: vec!["qg==".to_owned()],
+            root_token: "hvs.root".to_owned(),
+            ..Default::default()
+        })
+    }
+
+    /// Emergency fallback encrypting to a new key pair, read from a file as
+    /// configured, and the private key to decrypt with.
+    async fn key_sink(dir: &Path, path: Option<PathBuf>) -> (Sink, SignedSecretKey) {
+        let (private_key, public_key) = key_pair("");
+        let key_path = dir.join("public.pgp");
+        std::fs::write(
+            &key_path,
+            data_encoding::BASE64.decode(public_key.as_bytes()).unwrap(),
+        )
+        .unwrap();
+        let emergency = Emergency {
+            public_key: Some(KeySource {
+                file: Some(key_path),
+                ..Default::default()
+            }),
+            path,
+            disabled: None,
+        };
+        (emergency.sink().await.unwrap().unwrap(), private_key)
+    }
+
+    fn decrypt(armored: &str, private_key: &SignedSecretKey) -> Envelope {
+        let message = Message::from_string(armored).unwrap().0;
+        let (decrypted, _) = message.decrypt(String::new, &[private_key]).unwrap();
+        Envelope::from_slice(&decrypted.get_content().unwrap().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn writes_encrypted_to_new_file() {
+        let dir = test_dir("write");
+        let path = dir.join("init.pgp");
+        let (sink, private_key) = key_sink(&dir, Some(path.clone())).await;
+        let envelope = init_data();
+
+        assert_eq!(sink.write(&envelope).unwrap(), path.display().to_string());
+        let armored = std::fs::read_to_string(&path).unwrap();
+        assert!(armored.starts_with("-----BEGIN PGP MESSAGE-----"));
+        assert!(!armored.contains("hvs.root"));
+        assert_eq!(decrypt(&armored, &private_key), envelope);
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[tokio::test]
+    async fn refuses_existing_file() {
+        let dir = test_dir("existing");
+        let path = dir.join("init.pgp");
+        std::fs::write(&path, "earlier init data").unwrap();
+        let (sink, _) = key_sink(&dir, Some(path.clone())).await;
+
+        assert_eq!(sink.write(&init_data()).unwrap(), "stderr");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "earlier init data");
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_stderr() {
+        let dir = test_dir("stderr");
+        let (sink, _) = key_sink(&dir, Some(dir.join("missing").join("init.pgp"))).await;
+        assert_eq!(sink.write(&init_data()).unwrap(), "stderr");
+
+        let (sink, _) = key_sink(&dir, None).await;
+        assert_eq!(sink.write(&init_data()).unwrap(), "stderr");
+    }
+
+    #[tokio::test]
+    async fn requires_public_key_unless_disabled() {
+        assert!(Emergency::default().sink().await.is_err());
+
+        let disabled = Emergency {
+            disabled: Some(true),
+            ..Default::default()
+        };
+        assert!(disabled.sink().await.unwrap().is_none());
+    }
+}
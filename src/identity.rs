@@ -0,0 +1,130 @@
+use anyhow::bail;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::debug;
+use tracing::error;
+use tracing::info;
+use tracing::warn;
+
+use crate::save::Metadata;
+use crate::vault::models::sys::seal_status::GetSealStatusResponse;
+use crate::vault::VaultClient;
+
+/// Checks that Vault is the server init data was created by before key shares
+/// are sent to it, so that they are not leaked to a spoofed endpoint. Vault
+/// does not report its cluster ID while sealed, so only the certificate check
+/// protects key shares sent to unseal it.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Identity {
+    /// Require Vault's certificate to have the pin recorded at init. Vault must
+    /// be reached over HTTPS. Disabled unless set to true.
+    pub certificate: Option<bool>,
+    /// Require Vault to report the cluster ID recorded at init, where it does.
+    /// Enabled unless set to false.
+    pub cluster_id: Option<bool>,
+}
+
+impl Identity {
+    /// Warns if key shares sent to unseal Vault are not protected, as the
+    /// certificate check is disabled. Over plain HTTP there is no certificate
+    /// to check, so this is only noted.
+    pub fn warn_unprotected(&self, vault: &VaultClient) {
+        if vault.addr.scheme() != "https" {
+            info!(
+                phase = "start",
+                "Vault is not reached over HTTPS, so its identity is only checked by cluster ID"
+            );
+        } else if !self.certificate.unwrap_or(false) {
+            warn!(
+                phase = "start",
+                "Vault's certificate is not checked against init data, so unseal keys can be \
+                 sent to a spoofed endpoint. Set `identity {{ certificate = true }}` to prevent it"
+            );
+        }
+    }
+
+    /// Refuses unless Vault matches the identity recorded in `metadata`.
+    pub async fn check(&self, vault: &VaultClient, metadata: &Metadata) -> anyhow::Result<()> {
+        let phase = "identity";
+
+        if self.cluster_id.unwrap_or(true) {
+            if let Some(expected) = &metadata.cluster_id {
+                let seal_status = vault.get_seal_status().await.inspect_err(|_| {
+                    error!(phase, "Failed checking status");
+                })?;
+                check_cluster_id(expected, &seal_status)?;
+            }
+        }
+
+        if self.certificate.unwrap_or(false) {
+            let Some(pin) = &metadata.server_pin_sha256 else {
+                let msg = "No certificate pin was recorded with init data, refusing to send keys. \
+                           It is recorded whenever init data is upgraded while Vault is unsealed";
+                error!(phase, msg);
+                bail!(msg);
+            };
+            vault.bind_server_pin(pin).inspect_err(|err| {
+                error!(phase, %err, "Vault certificate does not match init data");
+            })?;
+            info!(phase, "Vault certificate matches init data");
+        }
+
+        Ok(())
+    }
+}
+
+/// Refuses unless Vault reports the `expected` cluster ID, which it does only
+/// while unsealed.
+fn check_cluster_id(expected: &str, seal_status: &GetSealStatusResponse) -> anyhow::Result<()> {
+    let phase = "identity";
+    match &seal_status.cluster_id {
+        Some(actual) if actual != expected => {
+            let msg = format!(
+                "Vault reports cluster ID {actual}, but init data was created by {expected}, \
+                 refusing to send keys"
+            );
+            error!(phase, msg);
+            bail!(msg);
+        }
+        Some(_) => info!(phase, "Vault cluster ID matches init data"),
+        None if seal_status.sealed => {
+            debug!(phase, "Vault does not report its cluster ID while sealed");
+        }
+        None => {
+            let msg = "Vault does not report its cluster ID while unsealed, refusing to send keys";
+            error!(phase, msg);
+            bail!(msg);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seal_status(sealed: bool, cluster_id: Option<&str>) -> GetSealStatusResponse {
+        GetSealStatusResponse {
+            initialized: true,
+            sealed,
+            cluster_id: cluster_id.map(str::to_owned),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accepts_matching_cluster_id() {
+        assert!(check_cluster_id("a", &seal_status(false, Some("a"))).is_ok());
+    }
+
+    #[test]
+    fn refuses_other_cluster_id() {
+        assert!(check_cluster_id("a", &seal_status(false, Some("b"))).is_err());
+    }
+
+    #[test]
+    fn accepts_missing_cluster_id_only_while_sealed() {
+        assert!(check_cluster_id("a", &seal_status(true, None)).is_ok());
+        assert!(check_cluster_id("a", &seal_status(false, None)).is_err());
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+
+use anyhow::Context;
+use chrono::DateTime;
+use chrono::Utc;
+use k8s_openapi::api::core::v1::Secret;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::api::Patch;
+use kube::api::PatchParams;
+use kube::api::PostParams;
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::json;
+use tracing::error;
+use tracing::info;
+
+use crate::save::sync_parent;
+
+const DEFAULT_SECRET_NAME: &str = "vault-init-journal";
+const DEFAULT_ANNOTATION: &str = "vault-init/journal";
+
+/// Steps of a run, in the order they are taken.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    #[default]
+    Init,
+    /// Vault was initialized, and its init data is being saved.
+    Save,
+    Unseal,
+    Upgrade,
+    RotateRoot,
+    /// A new root token was generated, and is being saved.
+    SaveToken,
+    /// The new root token was saved, and the previous one is being revoked.
+    Revoke,
+    Done,
+}
+
+/// Progress of a run, written ahead of each step so that an interrupted run
+/// can resume. Never holds secret values.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    /// The step in progress. Every step before it is complete.
+    pub step: Step,
+    pub updated_at: Option<DateTime<Utc>>,
+    /// Nonce of the generate root attempt started by this run.
+    pub generate_root_nonce: Option<String>,
+    /// Accessor of the root token to revoke once its replacement is saved.
+    pub previous_root_accessor: Option<String>,
+    /// Accessor of the root token generated by this run until it is saved, so
+    /// that a run interrupted before then can revoke it.
+    pub new_root_accessor: Option<String>,
+}
+
+/// Where the journal is persisted. If neither is set, it is only kept in
+/// memory and an interrupted run starts over.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Journal {
+    pub path: Option<PathBuf>,
+    pub kube_secret: Option<KubeSecretAnnotation>,
+}
+
+/// An annotation on a Kubernetes secret, which is created if it does not
+/// exist. This should not be the secret of a save method, which replaces its
+/// annotations when saving.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct KubeSecretAnnotation {
+    pub name: Option<String>,
+    pub namespace: Option<String>,
+    pub annotation: Option<String>,
+}
+
+#[async_trait::async_trait]
+pub trait Store: Send + Sync {
+    async fn read(&self) -> anyhow::Result<Option<Record>>;
+
+    async fn write(&self, record: &Record) -> anyhow::Result<()>;
+}
+
+/// The record of the current run, and the store it is written to.
+pub struct Progress {
+    store: Box<dyn Store>,
+    pub record: Record,
+}
+
+impl Journal {
+    /// Reads the journal, resuming an interrupted run. A finished run starts
+    /// over.
+    pub async fn open(&self) -> anyhow::Result<Progress> {
+        let store = self.store();
+        let record = match store.read().await? {
+            Some(record) if record.step != Step::Done => {
+                info!(phase = "journal", step = ?record.step, "Resuming interrupted run");
+                record
+            }
+            _ => Record::default(),
+        };
+        Ok(Progress { store, record })
+    }
+
+    fn store(&self) -> Box<dyn Store> {
+        if let Some(path) = &self.path {
+            Box::new(FileStore { path: path.clone() })
+        } else if let Some(annotation) = &self.kube_secret {
+            Box::new(annotation.clone())
+        } else {
+            Box::<MemoryStore>::default()
+        }
+    }
+}
+
+impl Progress {
+    pub fn step(&self) -> Step {
+        self.record.step
+    }
+
+    /// Records that the given step is about to be taken.
+    pub async fn advance(&mut self, step: Step) -> anyhow::Result<()> {
+        self.record.step = step;
+        self.write().await
+    }
+
+    /// Starts over from the first step, forgetting the progress made.
+    pub async fn restart(&mut self) -> anyhow::Result<()> {
+        self.record = Record::default();
+        self.write().await
+    }
+
+    pub async fn write(&mut self) -> anyhow::Result<()> {
+        self.record.updated_at = Some(Utc::now());
+        self.store.write(&self.record).await.inspect_err(|_| {
+            error!(phase = "journal", "Failed writing journal");
+        })
+    }
+}
+
+#[derive(Default)]
+struct MemoryStore {
+    record: Mutex<Option<Record>>,
+}
+
+#[async_trait::async_trait]
+impl Store for MemoryStore {
+    async fn read(&self) -> anyhow::Result<Option<Record>> {
+        Ok(self
+            .record
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone())
+    }
+
+    async fn write(&self, record: &Record) -> anyhow::Result<()> {
+        *self.record.lock().unwrap_or_else(PoisonError::into_inner) = Some(record.clone());
+        Ok(())
+    }
+}
+
+struct FileStore {
+    path: PathBuf,
+}
+
+#[async_trait::async_trait]
+impl Store for FileStore {
+    async fn read(&self) -> anyhow::Result<Option<Record>> {
+        match tokio::fs::read(&self.path).await {
+            Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => {
+                Err(err).with_context(|| format!("Failed reading journal: {}", self.path.display()))
+            }
+        }
+    }
+
+    async fn write(&self, record: &Record) -> anyhow::Result<()> {
+        let path = self.path.clone();
+        let bytes = serde_json::to_vec(record)?;
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            let mut tmp = path.clone().into_os_string();
+            tmp.push(".tmp");
+            let mut file = std::fs::OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .mode(0o600)
+                .open(&tmp)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+            std::fs::rename(&tmp, &path)?;
+            sync_parent(&path)
+        })
+        .await?
+        .with_context(|| format!("Failed writing journal: {}", self.path.display()))
+    }
+}
+
+impl KubeSecretAnnotation {
+    async fn api(&self) -> anyhow::Result<kube::Api<Secret>> {
+        let client = kube::Client::try_default().await?;
+        let secrets = match &self.namespace {
+            Some(ns) => kube::Api::namespaced(client, ns),
+            None => kube::Api::default_namespaced(client),
+        };
+        Ok(secrets)
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(DEFAULT_SECRET_NAME)
+    }
+
+    fn annotation(&self) -> &str {
+        self.annotation.as_deref().unwrap_or(DEFAULT_ANNOTATION)
+    }
+}
+
+#[async_trait::async_trait]
+impl Store for KubeSecretAnnotation {
+    async fn read(&self) -> anyhow::Result<Option<Record>> {
+        let Some(secret) = self.api().await?.get_opt(self.name()).await? else {
+            return Ok(None);
+        };
+        secret
+            .metadata
+            .annotations
+            .and_then(|mut annotations| annotations.remove(self.annotation()))
+            .map(|value| Ok(serde_json::from_str(&value)?))
+            .transpose()
+    }
+
+    async fn write(&self, record: &Record) -> anyhow::Result<()> {
+        let secrets = self.api().await?;
+        let value = serde_json::to_string(record)?;
+
+        if secrets.get_opt(self.name()).await?.is_none() {
+            let secret = Secret {
+                metadata: ObjectMeta {
+                    name: Some(self.name().to_owned()),
+                    annotations: Some(BTreeMap::from([(self.annotation().to_owned(), value)])),
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            secrets.create(&PostParams::default(), &secret).await?;
+            return Ok(());
+        }
+
+        let patch = json!({ "metadata": { "annotations": { self.annotation(): value } } });
+        secrets
+            .patch(self.name(), &PatchParams::default(), &Patch::Merge(&patch))
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn journal(name: &str) -> Journal {
+        let path = std::env::temp_dir().join(format!(
+            "vault-init-{}-journal-{name}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        Journal {
+            path: Some(path),
+            kube_secret: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn resumes_interrupted_run() {
+        let journal = journal("resume");
+        let mut progress = journal.open().await.unwrap();
+        assert_eq!(progress.step(), Step::Init);
+        progress.record.new_root_accessor = Some("accessor".to_owned());
+        progress.advance(Step::SaveToken).await.unwrap();
+
+        let progress = journal.open().await.unwrap();
+        assert_eq!(progress.step(), Step::SaveToken);
+        assert_eq!(
+            progress.record.new_root_accessor.as_deref(),
+            Some("accessor")
+        );
+    }
+
+    #[tokio::test]
+    async fn starts_over_after_finished_or_restarted_run() {
+        let journal = journal("restart");
+        let mut progress = journal.open().await.unwrap();
+        progress.advance(Step::Done).await.unwrap();
+        assert_eq!(journal.open().await.unwrap().step(), Step::Init);
+
+        progress.record.previous_root_accessor = Some("accessor".to_owned());
+        progress.advance(Step::Revoke).await.unwrap();
+        progress.restart().await.unwrap();
+        let progress = journal.open().await.unwrap();
+        assert_eq!(progress.step(), Step::Init);
+        assert_eq!(progress.record.previous_root_accessor, None);
+    }
+
+    #[test]
+    fn reads_record_without_new_fields() {
+        let record: Record = serde_json::from_str(r#"{"step":"revoke"}"#).unwrap();
+        assert_eq!(record.step, Step::Revoke);
+        assert_eq!(record.new_root_accessor, None);
+    }
+}
@@ -2,25 +2,55 @@
 #![allow(clippy::struct_excessive_bools)]
 #![allow(clippy::module_name_repetitions)]
 
+mod cmd;
 mod config;
+mod emergency;
+mod identity;
+mod journal;
+mod paper;
+mod pgp;
 mod save;
 mod vault;
 
+use std::path::Path;
 use std::path::PathBuf;
 
 use anyhow::bail;
 use clap::Parser;
+use clap::Subcommand;
 use tracing::debug;
 use tracing::error;
 use tracing::info;
+use tracing::warn;
 use tracing_subscriber::prelude::*;
 
+use crate::cmd::import::ImportFormat;
+use crate::cmd::import::ImportSource;
+use crate::cmd::inventory::InventoryFormat;
+use crate::cmd::paper::PaperFormat;
+use crate::cmd::status::StatusFormat;
 use crate::config::Config;
-use crate::vault::models::auth::token::PostRevokeRequest;
+use crate::emergency::Emergency;
+use crate::emergency::Sink;
+use crate::identity::Identity;
+use crate::journal::Progress;
+use crate::journal::Step;
+use crate::pgp::Decrypter;
+use crate::save::Envelope;
+use crate::save::Loaded;
+use crate::save::Registry;
+use crate::save::SaveFailed;
+use crate::save::SaveMethods;
+use crate::vault::models::auth::token::PostLookupAccessorRequest;
+use crate::vault::models::auth::token::PostRevokeAccessorRequest;
 use crate::vault::models::sys::generate_root::PostGenerateRootAttemptRequest;
 use crate::vault::models::sys::generate_root::PostGenerateRootUpdateRequest;
+use crate::vault::models::sys::health::GetHealthRequest;
 use crate::vault::models::sys::init::PostInitRequest;
 use crate::vault::models::sys::unseal::PostUnsealRequest;
+use crate::vault::Feature;
+use crate::vault::Server;
+use crate::vault::Tls;
 use crate::vault::VaultClient;
 
 #[allow(clippy::doc_markdown)]
@@ -28,16 +58,27 @@ use crate::vault::VaultClient;
 #[derive(Parser, Debug, Clone)]
 #[clap(author, version, about)]
 pub struct Args {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     /// Address of the Vault server expressed as a URL and port.
-    #[clap(long, env = "VAULT_ADDR", default_value = "http://127.0.0.1:8200")]
+    #[clap(
+        long,
+        env = "VAULT_ADDR",
+        default_value = "http://127.0.0.1:8200",
+        global = true
+    )]
     vault_addr: url::Url,
 
+    #[clap(flatten)]
+    tls: Tls,
+
     /// Level directive for stdout logging.
-    #[clap(long, env = "RUST_LOG", default_value = "info")]
+    #[clap(long, env = "RUST_LOG", default_value = "info", global = true)]
     log_level: String,
 
     /// Config file.
-    #[clap(long, short, default_value = "vault-init.hcl")]
+    #[clap(long, short, default_value = "vault-init.hcl", global = true)]
     config: PathBuf,
 
     /// Array of PGP public keys used to encrypt the output unseal keys.
@@ -93,72 +134,544 @@ pub struct Args {
     recovery_pgp_keys: Option<Vec<String>>,
 }
 
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Initialize and unseal Vault, then rotate the root token (default).
+    Run,
+
+    /// List the versions of init data retained by save methods.
+    ListVersions {
+        /// Only list versions retained by this save method.
+        #[clap(long)]
+        save_method: Option<String>,
+    },
+
+    /// Make a retained version of init data current again.
+    RestoreVersion {
+        /// Save method to restore the version in.
+        #[clap(long)]
+        save_method: String,
+
+        /// Version to restore, as shown by `list-versions`.
+        version: usize,
+    },
+
+    /// Write init data created by hand or by other tools to the save methods.
+    Import {
+        /// File or directory to read from. Read from stdin if not set.
+        #[clap(long, short, conflicts_with = "kube_secret")]
+        input: Option<PathBuf>,
+
+        /// Kubernetes secret to read from, holding one key per entry.
+        #[clap(long)]
+        kube_secret: Option<String>,
+
+        /// Namespace of the Kubernetes secret. The current namespace if not
+        /// set.
+        #[clap(long, requires = "kube_secret")]
+        namespace: Option<String>,
+
+        /// Input format.
+        #[clap(long, value_enum, default_value = "auto")]
+        format: ImportFormat,
+
+        /// Prefix of the entries holding each key, named `<prefix>-root`,
+        /// `<prefix>-unseal-N` and `<prefix>-recovery-N`.
+        #[clap(long, default_value = "vault")]
+        key_prefix: String,
+
+        /// Replace init data already held by the save methods.
+        #[clap(long)]
+        force: bool,
+    },
+
+    /// Describe the init data held by each save method, without revealing it.
+    Inventory {
+        /// Output format.
+        #[clap(long, value_enum, default_value = "table")]
+        format: InventoryFormat,
+    },
+
+    /// Render each key share on its own page for break-glass storage.
+    ExportPaper {
+        /// Output format.
+        #[clap(long, value_enum, default_value = "text")]
+        format: PaperFormat,
+
+        /// File to write to. Text is written to stdout if not set.
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+
+        /// Export recovery key shares instead of unseal key shares.
+        #[clap(long)]
+        recovery: bool,
+    },
+
+    /// Show the state of Vault, and whether the stored root token is still
+    /// valid.
+    Status {
+        /// Output format.
+        #[clap(long, value_enum, default_value = "table")]
+        format: StatusFormat,
+    },
+
+    /// Unseal Vault with shares typed from paper backups, one per line.
+    ImportPaper {
+        /// File to read shares from. Read from stdin if not set.
+        #[clap(long, short)]
+        input: Option<PathBuf>,
+    },
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> anyhow::Result<()> {
     let args = Args::parse();
     setup_logging(&args.log_level)?;
-    let vault = VaultClient::new(args.vault_addr.clone());
     info!(phase = "start", "Started process");
 
-    let config = args.config.clone();
-    debug!(phase = "start", ?config, "Reading config file");
-    let buf = tokio::fs::read(config).await?;
+    let command = args.command.clone().unwrap_or(Command::Run);
+    let config = read_config(&args.config, command.requires_config()).await?;
+    let tls = args.tls.clone().or(config.tls.clone().unwrap_or_default());
+    let retry = config.retry.clone().unwrap_or_default();
+    let headers = config.headers.clone().unwrap_or_default();
+    let proxy = config.proxy.clone().unwrap_or_default();
+    let vault = VaultClient::new(args.vault_addr.clone(), &tls, &retry, &headers, &proxy)?;
+    let save_methods = Registry::default().build(&config)?;
+
+    let result = match command {
+        Command::Run => {
+            let decrypter = config.pgp.clone().unwrap_or_default().decrypter().await;
+            run(&vault, &args, &save_methods, &decrypter, &config).await
+        }
+        Command::ListVersions { save_method } => {
+            cmd::versions::list_versions(&save_methods, save_method.as_deref()).await
+        }
+        Command::RestoreVersion {
+            save_method,
+            version,
+        } => cmd::versions::restore_version(&save_methods, &save_method, version).await,
+        Command::Import {
+            input,
+            kube_secret,
+            namespace,
+            format,
+            key_prefix,
+            force,
+        } => {
+            let source = match kube_secret.as_deref() {
+                Some(name) => ImportSource::KubeSecret {
+                    name,
+                    namespace: namespace.as_deref(),
+                },
+                None => ImportSource::Input(input.as_deref()),
+            };
+            cmd::import::import(&vault, &save_methods, source, format, &key_prefix, force).await
+        }
+        Command::Inventory { format } => cmd::inventory::inventory(&save_methods, format).await,
+        Command::ExportPaper {
+            format,
+            output,
+            recovery,
+        } => cmd::paper::export_paper(&save_methods, format, output.as_deref(), recovery).await,
+        Command::ImportPaper { input } => cmd::paper::import_paper(&vault, input.as_deref()).await,
+        Command::Status { format } => {
+            let decrypter = config.pgp.clone().unwrap_or_default().decrypter().await;
+            cmd::status::status(&vault, &args.vault_addr, &save_methods, &decrypter, format).await
+        }
+    };
+
+    // Keys exist only in the emergency fallback, which needs attention beyond
+    // an ordinary failure
+    if let Err(err) = &result {
+        if err.is::<emergency::Saved>() {
+            eprintln!("Error: {err:?}");
+            std::process::exit(emergency::EXIT_CODE);
+        }
+    }
+    result
+}
+
+impl Command {
+    /// Whether the command needs save methods from the config file. Others run
+    /// with an empty config if the file does not exist.
+    fn requires_config(&self) -> bool {
+        !matches!(self, Command::ImportPaper { .. })
+    }
+}
+
+async fn read_config(path: &Path, required: bool) -> anyhow::Result<Config> {
+    debug!(phase = "start", config = ?path, "Reading config file");
+    let buf = match tokio::fs::read(path).await {
+        Ok(buf) => buf,
+        Err(err) if !required && err.kind() == std::io::ErrorKind::NotFound => {
+            debug!(phase = "start", "Config file not found, using defaults");
+            return Ok(Config::default());
+        }
+        Err(err) => return Err(err.into()),
+    };
     let config: Config = hcl::from_slice(&buf)?;
     debug!(phase = "start", ?config, "Read config file");
+    Ok(config)
+}
 
-    // Ensure init ------------------------------------------------------------
+async fn run(
+    vault: &VaultClient,
+    args: &Args,
+    save_methods: &SaveMethods,
+    decrypter: &Decrypter,
+    config: &Config,
+) -> anyhow::Result<()> {
+    let identity = &config.identity.clone().unwrap_or_default();
+    identity.warn_unprotected(vault);
+    let emergency = &config.emergency.clone().unwrap_or_default();
+    let deadlines = &config.retry.clone().unwrap_or_default().deadline;
+
+    let server = wait_ready(&vault.until(deadlines.ready())).await?;
+
+    let journal = config.journal.clone().unwrap_or_default();
+    let mut progress = journal.open().await?;
+
+    if progress.step() > Step::Unseal {
+        let vault = &vault.until(deadlines.step(Step::Unseal));
+        resume(vault, save_methods, decrypter, identity, &mut progress).await?;
+    }
 
+    // Generated by this run, and only held in memory until it is saved
+    let mut new_root_token = None;
+
+    // Vault is unsealed again at most once per run
+    let mut unsealed_again = false;
+
+    loop {
+        let vault = &vault.until(deadlines.step(progress.step()));
+        let step = progress.step();
+        if step == Step::Done {
+            return Ok(());
+        }
+        let result = async {
+            Ok(match step {
+                Step::Init => {
+                    ensure_init(vault, args, save_methods, emergency, &mut progress).await?
+                }
+                Step::Save => check_saved(vault, save_methods).await?,
+                Step::Unseal => {
+                    ensure_unseal(vault, save_methods, decrypter, identity).await?;
+                    Step::Upgrade
+                }
+                Step::Upgrade => {
+                    if let Err(err) = upgrade_and_save(vault, save_methods).await {
+                        warn!(phase = "upgrade", ?err, "Failed upgrading stored init data");
+                    }
+                    Step::RotateRoot
+                }
+                Step::RotateRoot
+                    if !generates_root(vault, save_methods, decrypter, server).await? =>
+                {
+                    Step::Done
+                }
+                Step::RotateRoot => {
+                    let root_token =
+                        rotate_root(vault, save_methods, decrypter, identity, &mut progress)
+                            .await?;
+                    progress.record.new_root_accessor =
+                        Some(new_root_accessor(vault, &root_token).await?);
+                    new_root_token = Some(root_token);
+                    Step::SaveToken
+                }
+                Step::SaveToken => {
+                    if let Some(root_token) = &new_root_token {
+                        save_root_token(save_methods, args, root_token).await?;
+                        progress.record.new_root_accessor = None;
+                        Step::Revoke
+                    } else {
+                        recover_new_root(vault, save_methods, decrypter, &mut progress).await?
+                    }
+                }
+                Step::Revoke => {
+                    revoke_previous_root(
+                        vault,
+                        save_methods,
+                        decrypter,
+                        &progress,
+                        new_root_token.as_deref(),
+                    )
+                    .await?;
+                    Step::Done
+                }
+                Step::Done => Step::Done,
+            })
+        }
+        .await;
+        let next = match result {
+            Ok(next) => next,
+            Err(err) => {
+                let unsealed_again = &mut unsealed_again;
+                recover_step(
+                    vault,
+                    save_methods,
+                    decrypter,
+                    identity,
+                    step,
+                    err,
+                    unsealed_again,
+                )
+                .await?
+            }
+        };
+        progress.advance(next).await?;
+    }
+}
+
+/// Checks Vault before resuming an interrupted run past unsealing, as it may
+/// have restarted since, or even been wiped.
+async fn resume(
+    vault: &VaultClient,
+    save_methods: &SaveMethods,
+    decrypter: &Decrypter,
+    identity: &Identity,
+    progress: &mut Progress,
+) -> anyhow::Result<()> {
+    let init_status = vault.read_init_status().await.inspect_err(|_| {
+        error!(phase = "journal", "Failed checking status");
+    })?;
+    if init_status.initialized {
+        ensure_unseal(vault, save_methods, decrypter, identity).await?;
+    } else {
+        warn!(
+            phase = "journal",
+            step = ?progress.step(),
+            "Vault is no longer initialized, starting over"
+        );
+        progress.restart().await?;
+    }
+    Ok(())
+}
+
+/// Recovers from a step failing as Vault changed state during the run, giving
+/// the step to take next, or the error if it cannot. Vault sealed again, such
+/// as by a restart, is unsealed unless it already was, and the step retried.
+async fn recover_step(
+    vault: &VaultClient,
+    save_methods: &SaveMethods,
+    decrypter: &Decrypter,
+    identity: &Identity,
+    step: Step,
+    err: anyhow::Error,
+    unsealed_again: &mut bool,
+) -> anyhow::Result<Step> {
+    match err.downcast_ref::<vault::Error>() {
+        Some(vault::Error::Sealed) if !*unsealed_again && step > Step::Unseal => {
+            *unsealed_again = true;
+            warn!(
+                phase = "unseal",
+                ?step,
+                "Vault was sealed again, such as by a restart, unsealing it before retrying"
+            );
+            ensure_unseal(vault, save_methods, decrypter, identity).await?;
+            Ok(step)
+        }
+        Some(vault::Error::Standby { .. }) if step == Step::RotateRoot => {
+            info!(
+                phase = "rotate_root",
+                "Vault stepped down to a standby node, leaving generating root to the active node"
+            );
+            Ok(Step::Done)
+        }
+        _ => Err(err),
+    }
+}
+
+/// Waits for Vault to respond, which it does whether or not it is initialized
+/// or unsealed, and detects the server it runs.
+async fn wait_ready(vault: &VaultClient) -> anyhow::Result<Server> {
+    let phase = "ready";
+
+    info!(phase, "Waiting for Vault to respond");
+    let seal_status = vault.get_seal_status().await.inspect_err(|_| {
+        error!(phase, "Failed waiting for Vault to respond");
+    })?;
+    let server = Server::detect(&seal_status);
+    info!(
+        phase,
+        flavor = %server.flavor,
+        version = seal_status.version,
+        "Vault is ready"
+    );
+
+    Ok(server)
+}
+
+async fn ensure_init(
+    vault: &VaultClient,
+    args: &Args,
+    save_methods: &SaveMethods,
+    emergency: &Emergency,
+    progress: &mut Progress,
+) -> anyhow::Result<Step> {
     info!(phase = "init", "Checking status");
-    let init_status = vault.read_init_status().await.map_err(|err| {
+    let init_status = vault.read_init_status().await.inspect_err(|_| {
         error!(phase = "init", "Failed checking status");
-        err
     })?;
     if init_status.initialized {
         info!(phase = "init", "Vault is already initialized");
-    } else {
-        info!(phase = "init", "Vault is uninitialized");
-        init_and_save(&vault, args.clone(), &config).await?;
+        return Ok(Step::Unseal);
     }
 
-    // Ensure unseal ----------------------------------------------------------
+    info!(phase = "init", "Vault is uninitialized");
+    let sink = emergency.sink().await.inspect_err(|_| {
+        error!(phase = "init", "Failed preparing emergency fallback");
+    })?;
+    progress.advance(Step::Save).await?;
+    init_and_save(vault, args.clone(), save_methods, sink.as_ref()).await?;
+
+    Ok(Step::Unseal)
+}
 
+/// Checks that init data was saved by a run interrupted while initializing.
+async fn check_saved(vault: &VaultClient, save_methods: &SaveMethods) -> anyhow::Result<Step> {
+    let phase = "init";
+
+    info!(phase, "Checking status after interrupted initialization");
+    let init_status = vault.read_init_status().await.inspect_err(|_| {
+        error!(phase, "Failed checking status");
+    })?;
+    if !init_status.initialized {
+        info!(phase, "Vault is uninitialized");
+        return Ok(Step::Init);
+    }
+
+    if let Err(err) = save_methods.load_init_all().await {
+        let msg = "Vault was initialized by an interrupted run, but its init data was not saved, \
+                   check the emergency fallback";
+        error!(phase, ?err, msg);
+        bail!(msg);
+    }
+    info!(phase, "Init data from interrupted run was saved");
+
+    Ok(Step::Unseal)
+}
+
+async fn ensure_unseal(
+    vault: &VaultClient,
+    save_methods: &SaveMethods,
+    decrypter: &Decrypter,
+    identity: &Identity,
+) -> anyhow::Result<()> {
     info!(phase = "unseal", "Checking status");
-    let seal_status = vault.get_seal_status().await.map_err(|err| {
+    let seal_status = vault.get_seal_status().await.inspect_err(|_| {
         error!(phase = "unseal", "Failed checking status");
-        err
     })?;
-    if seal_status.sealed {
+    if seal_status.sealed && seal_status.recovery_seal() {
+        // Keys cannot unseal it, but it is sealed until it reaches its seal,
+        // such as a KMS
+        info!(
+            phase = "unseal",
+            seal_type = seal_status.r#type,
+            "Vault is sealed, and uses Auto Unseal"
+        );
+        wait_auto_unseal(vault).await?;
+    } else if seal_status.sealed {
         info!(phase = "unseal", "Vault is sealed");
-        load_and_unseal(&vault, &config).await?;
+        load_and_unseal(vault, save_methods, decrypter, identity, seal_status.t).await?;
     } else {
         info!(phase = "unseal", "Vault is already unsealed");
     }
 
-    // Rotate root ------------------------------------------------------------
+    Ok(())
+}
 
-    rotate_root(&vault, &config).await?;
+/// Waits for an Auto Unseal cluster to unseal itself, until the deadline of
+/// the client.
+async fn wait_auto_unseal(vault: &VaultClient) -> anyhow::Result<()> {
+    let phase = "unseal";
 
-    Ok(())
+    info!(phase, "Waiting for Vault to unseal itself");
+    let mut backoff = vault.backoff();
+    loop {
+        let Some(delay) = backoff.next() else {
+            let msg = "Vault uses Auto Unseal, but did not unseal itself before the deadline";
+            error!(phase, msg);
+            bail!(msg);
+        };
+        tokio::time::sleep(delay).await;
+        let seal_status = vault.get_seal_status().await.inspect_err(|_| {
+            error!(phase, "Failed checking status");
+        })?;
+        if !seal_status.sealed {
+            info!(phase, "Vault unsealed itself");
+            return Ok(());
+        }
+    }
 }
 
-async fn init_and_save(vault: &VaultClient, args: Args, config: &Config) -> anyhow::Result<()> {
+async fn init_and_save(
+    vault: &VaultClient,
+    args: Args,
+    save_methods: &SaveMethods,
+    sink: Option<&Sink>,
+) -> anyhow::Result<()> {
     info!(phase = "init", "Performing initialization");
     let init_request = PostInitRequest::from(args);
-    let init_response = vault.start_init(&init_request).await.map_err(|err| {
-        error!(phase = "init", "Failed performing initialization");
-        err
-    })?;
+    let init_response = match vault.start_init(&init_request).await {
+        Ok(init_response) => init_response,
+        // Another process initialized Vault since its status was checked, and
+        // is responsible for saving its init data
+        Err(err) if err.is_already_initialized() => {
+            warn!(
+                phase = "init",
+                "Vault was initialized by another process, continuing with its init data"
+            );
+            return Ok(());
+        }
+        Err(err) => {
+            error!(phase = "init", %err, "Failed performing initialization");
+            return Err(err.into());
+        }
+    };
     info!(phase = "init", "Successfully initialized Vault");
 
+    let mut envelope = Envelope::new(init_response);
+    match vault.get_seal_status().await {
+        Ok(seal_status) => envelope.metadata.update(&seal_status),
+        Err(err) => warn!(
+            phase = "init",
+            ?err,
+            "Failed reading metadata from seal status"
+        ),
+    }
+    envelope.metadata.server_pin_sha256 = vault.server_pin();
+
     info!(phase = "init", "Writing init data to save methods");
-    config
-        .save_method
-        .save_init_all(&init_response)
-        .await
-        .map_err(|err| {
-            error!(phase = "init", "Failed writing init data to save methods");
-            err
+    if let Err(err) = save_methods.save_init_all(&envelope).await {
+        // Only fall back if the save methods written cannot be read from alone
+        if err
+            .downcast_ref::<SaveFailed>()
+            .is_some_and(|err| err.readable)
+        {
+            warn!(
+                phase = "init",
+                ?err,
+                "Failed writing init data to some save methods, but enough hold it to read it \
+                 back"
+            );
+            return Ok(());
+        }
+        error!(
+            phase = "init",
+            ?err,
+            "Failed writing init data to save methods"
+        );
+        let Some(sink) = sink else {
+            return Err(err);
+        };
+        let location = sink.write(&envelope).inspect_err(|_| {
+            error!(
+                phase = "init",
+                "Failed writing init data to emergency fallback, keys are lost"
+            );
         })?;
+        return Err(emergency::Saved { location }.into());
+    }
     info!(
         phase = "init",
         "Successfully wrote init data to save methods"
@@ -167,22 +680,36 @@ async fn init_and_save(vault: &VaultClient, args: Args, config: &Config) -> anyh
     Ok(())
 }
 
-async fn load_and_unseal(vault: &VaultClient, config: &Config) -> anyhow::Result<()> {
+async fn load_and_unseal(
+    vault: &VaultClient,
+    save_methods: &SaveMethods,
+    decrypter: &Decrypter,
+    identity: &Identity,
+    threshold: i64,
+) -> anyhow::Result<()> {
+    // Key shares may be spread across save methods, so only read as many as
+    // are needed
     info!(phase = "unseal", "Reading init data from save methods");
-    let init_response = config.save_method.load_init_all().await.map_err(|err| {
-        error!(
-            phase = "unseal",
-            "Failed reading init data from save methods"
-        );
-        err
-    })?;
+    let threshold = usize::try_from(threshold)?;
+    let envelope = save_methods
+        .load_key_shares(threshold)
+        .await
+        .inspect_err(|_| {
+            error!(
+                phase = "unseal",
+                "Failed reading init data from save methods"
+            );
+        })?;
     info!(
         phase = "unseal",
         "Successfully read init data from save methods"
     );
 
+    identity.check(vault, &envelope.metadata).await?;
+
     info!(phase = "unseal", "Starting key submission process");
-    for (i, key) in init_response.keys.iter().enumerate() {
+    let keys = decrypter.keys(envelope.key_shares()?, false)?;
+    for (i, key) in keys.iter().enumerate() {
         info!(phase = "unseal", "Submitting key #{i}");
         let unseal_request = PostUnsealRequest {
             key: Some(key.clone()),
@@ -190,9 +717,12 @@ async fn load_and_unseal(vault: &VaultClient, config: &Config) -> anyhow::Result
             migrate: false,
         };
 
-        let Ok(unseal_response) = vault.submit_unseal_key(&unseal_request).await else {
-            error!(phase = "unseal", "Failed submitting key #{i}");
-            continue;
+        let unseal_response = match vault.submit_unseal_key(&unseal_request).await {
+            Ok(unseal_response) => unseal_response,
+            Err(err) => {
+                error!(phase = "unseal", %err, "Failed submitting key #{i}");
+                continue;
+            }
         };
         if !unseal_response.sealed {
             info!(phase = "unseal", "Successfully unsealed Vault");
@@ -203,73 +733,518 @@ async fn load_and_unseal(vault: &VaultClient, config: &Config) -> anyhow::Result
     Err(anyhow::anyhow!("Unable to completely unseal Vault"))
 }
 
-async fn rotate_root(vault: &VaultClient, config: &Config) -> anyhow::Result<()> {
+async fn upgrade_and_save(vault: &VaultClient, save_methods: &SaveMethods) -> anyhow::Result<()> {
+    let phase = "upgrade";
+
+    info!(phase, "Reading init data from save methods");
+    let Loaded {
+        mut envelope,
+        stale,
+    } = save_methods.load().await?;
+    if envelope.is_legacy() {
+        info!(phase, "Found init data in legacy format");
+    }
+
+    let seal_status = vault.get_seal_status().await?;
+    let previous = envelope.clone();
+    envelope.upgrade(&seal_status);
+    if envelope.metadata.server_pin_sha256.is_none() {
+        envelope.metadata.server_pin_sha256 = vault.server_pin();
+    }
+    if envelope == previous && !stale {
+        info!(phase, "Stored init data is up to date");
+        return Ok(());
+    }
+
+    info!(phase, "Writing upgraded init data to save methods");
+    save_methods.save_init_all(&envelope).await?;
+    info!(
+        phase,
+        "Successfully wrote upgraded init data to save methods"
+    );
+
+    Ok(())
+}
+
+/// Whether this run generates root, which Vault must support, and only the
+/// active node does.
+async fn generates_root(
+    vault: &VaultClient,
+    save_methods: &SaveMethods,
+    decrypter: &Decrypter,
+    server: Server,
+) -> anyhow::Result<bool> {
     let phase = "rotate_root";
 
-    // TODO: Consider if an in-progress genroot is actually a failure condition or
-    // if it could be resumed Check if generate root is already in progress and
-    // fail if so
-    info!(phase = "rotate_root", "Checking generate root status");
-    let genroot_status = vault.get_generate_root_attempt().await.map_err(|err| {
+    let server = with_history(vault, save_methods, decrypter, server).await;
+    if !server.supports(Feature::GenerateRootOtp) {
+        // Older versions require a one-time password to be sent, which is not
+        // supported
+        warn!(
+            phase,
+            flavor = %server.flavor,
+            "Generating root requires Vault 1.10 or later, leaving the root token as is"
+        );
+        return Ok(false);
+    }
+    if !is_active(vault, phase).await? {
+        // Every node may run vault-init to be unsealed, but only the active
+        // node generates root
+        info!(phase, "Leaving generating root to the active node");
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// The server as detected, told apart from `OpenBao` by its version history,
+/// which is read with the stored root token now that Vault is unsealed. As
+/// detected if it cannot be read.
+async fn with_history(
+    vault: &VaultClient,
+    save_methods: &SaveMethods,
+    decrypter: &Decrypter,
+    server: Server,
+) -> Server {
+    let phase = "rotate_root";
+
+    let root_token = match save_methods.load_root_token().await {
+        Ok(envelope) => envelope
+            .root_token()
+            .and_then(|root_token| decrypter.root_token(root_token)),
+        Err(err) => Err(err),
+    };
+    let root_token = match root_token {
+        Ok(Some(root_token)) => root_token,
+        Ok(None) => {
+            debug!(
+                phase,
+                "Stored root token is encrypted, not reading version history"
+            );
+            return server;
+        }
+        Err(err) => {
+            debug!(
+                phase,
+                ?err,
+                "Failed reading root token, not reading version history"
+            );
+            return server;
+        }
+    };
+    match vault
+        .with_token(root_token.into())
+        .get_version_history()
+        .await
+    {
+        Ok(history) => {
+            let detected = server.with_history(&history.data);
+            if detected != server {
+                info!(phase, flavor = %detected.flavor, "Detected server from its version history");
+            }
+            detected
+        }
+        Err(err) => {
+            debug!(phase, %err, "Failed reading version history");
+            server
+        }
+    }
+}
+
+/// Whether Vault is the active node of its cluster, which generating root and
+/// revoking tokens must be done on. DR secondaries never are.
+async fn is_active(vault: &VaultClient, phase: &str) -> anyhow::Result<bool> {
+    info!(phase, "Checking Vault is the active node");
+    let health = vault
+        .get_health(&GetHealthRequest::any_state())
+        .await
+        .inspect_err(|_| {
+            error!(phase, "Failed checking health");
+        })?;
+    if health.sealed {
+        let msg = "Vault is sealed";
+        error!(phase, msg);
+        bail!(msg);
+    }
+    if health.is_dr_secondary() {
+        // Its root token and generating root are those of the primary cluster
+        warn!(
+            phase,
+            "Vault is a DR secondary node, which is left to the primary cluster"
+        );
+        return Ok(false);
+    }
+    if !health.standby && !health.performance_standby {
+        info!(phase, "Vault is the active node");
+        return Ok(true);
+    }
+
+    let kind = if health.performance_standby {
+        "a performance standby"
+    } else {
+        "a standby"
+    };
+    match vault.get_leader().await {
+        Ok(leader) if !leader.leader_address.is_empty() => {
+            warn!(
+                phase,
+                active = leader.leader_address,
+                "Vault is {kind} node"
+            );
+        }
+        Ok(_) => warn!(phase, "Vault is {kind} node"),
+        Err(err) => warn!(phase, %err, "Vault is {kind} node, and the active node is unknown"),
+    }
+    Ok(false)
+}
+
+/// Generates a new root token.
+async fn rotate_root(
+    vault: &VaultClient,
+    save_methods: &SaveMethods,
+    decrypter: &Decrypter,
+    identity: &Identity,
+    progress: &mut Progress,
+) -> anyhow::Result<String> {
+    let phase = "rotate_root";
+
+    info!(phase, "Checking generate root status");
+    let genroot_status = vault.get_generate_root_attempt().await.inspect_err(|_| {
         error!(phase, "Failed checking generate root status");
-        err
     })?;
     if genroot_status.started {
-        let msg = "Generate root process is already in progress";
-        error!(phase, msg);
-        bail!(msg);
+        // An attempt started by an interrupted run cannot be completed, as its
+        // one-time password was lost with it
+        if progress.record.generate_root_nonce.as_ref() != Some(&genroot_status.nonce) {
+            let msg = "Generate root process is already in progress";
+            error!(phase, msg);
+            bail!(msg);
+        }
+        info!(phase, "Cancelling generate root process of interrupted run");
+        vault
+            .delete_generate_root_attempt()
+            .await
+            .inspect_err(|_| {
+                error!(phase, "Failed cancelling generate root process");
+            })?;
     }
     info!(phase, "Generate root process is not in progress");
 
-    // Load init response (containing root token)
+    // Load init data (containing root token)
     info!(phase, "Reading init data from save methods");
-    let init_response = config.save_method.load_init_all().await.map_err(|err| {
+    let envelope = save_methods.load_init_all().await.inspect_err(|_| {
         error!(phase, "Failed reading init data from save methods");
-        err
     })?;
     info!(phase, "Successfully read init data from save methods");
 
+    // The new root token is saved with all key shares, so they must all be
+    // available before starting
+    let data = envelope.data().inspect_err(|_| {
+        error!(
+            phase,
+            "Init data is incomplete, cannot save a new root token"
+        );
+    })?;
+
+    identity.check(vault, &envelope.metadata).await?;
+
+    // Auto Unseal clusters generate root with recovery keys instead
+    let seal_status = vault.get_seal_status().await?;
+    let recovery_seal = seal_status.recovery_seal();
+    let kind = if recovery_seal { "recovery key" } else { "key" };
+    let keys = decrypter.keys(data, recovery_seal)?;
+    if keys.is_empty() {
+        let msg = format!("Init data contains no usable {kind}s to generate root with");
+        error!(phase, msg);
+        bail!(msg);
+    }
+
+    // Remember which token to revoke, as the previous token may no longer be
+    // readable once the new one is saved
+    progress.record.previous_root_accessor =
+        root_token_accessor(vault, decrypter, &data.root_token).await;
+
     // Start generate root process
     info!(phase, "Starting generate root process");
     let genroot_start_request = PostGenerateRootAttemptRequest { pgp_key: None };
     let genroot_start_response = vault
         .post_generate_root_attempt(&genroot_start_request)
         .await
-        .map_err(|err| {
+        .inspect_err(|_| {
             error!(phase, "Failed starting generate root process");
-            err
         })?;
     info!(phase, "Successfully started generate root process");
 
     let nonce = genroot_start_response.nonce;
     let otp = genroot_start_response.otp;
+    progress.record.generate_root_nonce = Some(nonce.clone());
+    progress.write().await?;
 
-    for (i, key) in init_response.keys.iter().enumerate() {
-        info!(phase, "Submitting key #{i}");
+    for (i, key) in keys.iter().enumerate() {
+        info!(phase, "Submitting {kind} #{i}");
         let genroot_update_request = PostGenerateRootUpdateRequest {
             key: key.clone(),
             nonce: nonce.clone(),
         };
 
-        let Ok(genroot_update_response) = vault.post_generate_root_update(&genroot_update_request).await else {
-            error!(phase, "Failed submitting key #{i}");
-            continue;
+        let genroot_update_response = match vault
+            .post_generate_root_update(&genroot_update_request)
+            .await
+        {
+            Ok(genroot_update_response) => genroot_update_response,
+            Err(err) => {
+                error!(phase, %err, "Failed submitting {kind} #{i}");
+                continue;
+            }
         };
         if genroot_update_response.complete {
             info!(phase, "Successfully generated root");
 
-            // FIXME: Save
+            let root_token = genroot_update_response
+                .decode_token(&otp)
+                .inspect_err(|_| {
+                    error!(phase, "Failed decoding new root token");
+                })?;
+            progress.record.generate_root_nonce = None;
+
+            return Ok(root_token);
+        }
+    }
+
+    // The attempt is left in progress, to be cancelled by the next run
+    Err(anyhow::anyhow!("Unable to complete generate root process"))
+}
 
-            // FIXME:
-            info!(phase, "Revoking previous root token");
-            let vault = vault.with_token(init_response.root_token.into());
-            vault.post_auth_token_revoke_self().await?;
-            info!(phase, "Successfully revoked previous root token");
+/// Accessor of a root token from init data, or `None` if it cannot be looked
+/// up.
+async fn root_token_accessor(
+    vault: &VaultClient,
+    decrypter: &Decrypter,
+    root_token: &str,
+) -> Option<String> {
+    let phase = "rotate_root";
 
+    let Ok(Some(root_token)) = decrypter.root_token(root_token) else {
+        return None;
+    };
+    match vault
+        .with_token(root_token.into())
+        .get_auth_token_lookup_self()
+        .await
+    {
+        Ok(lookup) => {
+            info!(
+                phase,
+                expire_time = lookup.data.expire_time,
+                "Previous root token is valid"
+            );
+            Some(lookup.data.accessor)
+        }
+        Err(vault::Error::PermissionDenied { .. }) => {
+            warn!(
+                phase,
+                "Previous root token is no longer valid, so there is none to revoke"
+            );
+            None
+        }
+        Err(err) => {
+            warn!(phase, %err, "Failed looking up previous root token");
+            None
+        }
+    }
+}
+
+/// Looks up the accessor of a newly generated root token, so that it can be
+/// revoked if the run is interrupted before it is saved. The token is revoked
+/// right away if it cannot be looked up.
+async fn new_root_accessor(vault: &VaultClient, root_token: &str) -> anyhow::Result<String> {
+    let phase = "rotate_root";
+
+    let vault = vault.with_token(root_token.to_owned().into());
+    match vault.get_auth_token_lookup_self().await {
+        Ok(lookup) => Ok(lookup.data.accessor),
+        Err(err) => {
+            error!(phase, "Failed looking up new root token, revoking it");
+            if let Err(err) = vault.post_auth_token_revoke_self().await {
+                error!(phase, %err, "Failed revoking new root token");
+            }
+            Err(err.into())
+        }
+    }
+}
+
+/// Handles a run interrupted after generating a new root token, which is
+/// lost with it unless it was saved. If the stored root token is the new one,
+/// the run continues with revoking the previous one. Otherwise the new one is
+/// revoked by its accessor, and root is generated again.
+async fn recover_new_root(
+    vault: &VaultClient,
+    save_methods: &SaveMethods,
+    decrypter: &Decrypter,
+    progress: &mut Progress,
+) -> anyhow::Result<Step> {
+    let phase = "rotate_root";
+
+    let Some(accessor) = progress.record.new_root_accessor.clone() else {
+        warn!(
+            phase,
+            "New root token from an interrupted run was not saved, generating root again"
+        );
+        return Ok(Step::RotateRoot);
+    };
+
+    let envelope = save_methods.load_root_token().await.inspect_err(|_| {
+        error!(phase, "Failed reading root token from save methods");
+    })?;
+    let Some(root_token) = decrypter.root_token(envelope.root_token()?)? else {
+        warn!(
+            phase,
+            "Stored root token is encrypted with PGP and could not be decrypted, so the new \
+             root token from an interrupted run was not revoked"
+        );
+        progress.record.new_root_accessor = None;
+        return Ok(Step::RotateRoot);
+    };
+    let vault = vault.with_token(root_token.into());
+
+    let lookup = vault.get_auth_token_lookup_self().await.inspect_err(|_| {
+        error!(phase, "Failed looking up stored root token");
+    })?;
+    if lookup.data.accessor == accessor {
+        info!(phase, "New root token from an interrupted run was saved");
+        progress.record.new_root_accessor = None;
+        return Ok(Step::Revoke);
+    }
+
+    info!(
+        phase,
+        "Revoking new root token from an interrupted run, which was not saved"
+    );
+    match vault
+        .post_auth_token_revoke_accessor(&PostRevokeAccessorRequest { accessor })
+        .await
+    {
+        Ok(()) => info!(phase, "Successfully revoked unsaved root token"),
+        Err(err) if err.is_invalid_accessor() => {
+            info!(phase, "Unsaved root token was already revoked");
+        }
+        Err(err) => {
+            error!(phase, "Failed revoking unsaved root token");
+            return Err(err.into());
+        }
+    }
+    progress.record.new_root_accessor = None;
+
+    Ok(Step::RotateRoot)
+}
+
+/// Saves the new root token, encrypted with `--root-token-pgp-key` if set as
+/// the initial root token was.
+async fn save_root_token(
+    save_methods: &SaveMethods,
+    args: &Args,
+    root_token: &str,
+) -> anyhow::Result<()> {
+    let phase = "rotate_root";
+
+    let root_token = match &args.root_token_pgp_key {
+        Some(public_key) => pgp::encrypt(public_key, root_token).inspect_err(|_| {
+            error!(phase, "Failed encrypting new root token with PGP");
+        })?,
+        None => root_token.to_owned(),
+    };
+
+    info!(phase, "Writing new root token to save methods");
+    let mut envelope = save_methods.load_init_all().await.inspect_err(|_| {
+        error!(phase, "Failed reading init data from save methods");
+    })?;
+    envelope.rotate_root_token(root_token)?;
+    save_methods
+        .save_root_token(&envelope)
+        .await
+        .inspect_err(|_| {
+            error!(phase, "Failed writing new root token to save methods");
+        })?;
+    info!(phase, "Successfully wrote new root token to save methods");
+
+    Ok(())
+}
+
+/// Revokes the previous root token by its accessor, using the new root token
+/// generated by this run, or as saved if the run was resumed.
+async fn revoke_previous_root(
+    vault: &VaultClient,
+    save_methods: &SaveMethods,
+    decrypter: &Decrypter,
+    progress: &Progress,
+    new_root_token: Option<&str>,
+) -> anyhow::Result<()> {
+    let phase = "rotate_root";
+
+    info!(phase, "Revoking previous root token");
+    let Some(accessor) = progress.record.previous_root_accessor.clone() else {
+        warn!(
+            phase,
+            "Previous root token could not be looked up, so it was not revoked"
+        );
+        return Ok(());
+    };
+
+    if !is_active(vault, phase).await? {
+        let msg = "Vault is not the active node, so the previous root token cannot be revoked \
+                   until it is run against the active node";
+        error!(phase, msg);
+        bail!(msg);
+    }
+
+    let root_token = if let Some(root_token) = new_root_token {
+        root_token.to_owned()
+    } else {
+        let envelope = save_methods.load_root_token().await.inspect_err(|_| {
+            error!(phase, "Failed reading root token from save methods");
+        })?;
+        let Some(root_token) = decrypter.root_token(envelope.root_token()?)? else {
+            warn!(
+                phase,
+                "New root token is encrypted with PGP and could not be decrypted, so the \
+                 previous one was not revoked"
+            );
+            return Ok(());
+        };
+        root_token
+    };
+    let vault = vault.with_token(root_token.into());
+
+    // A resumed run may have revoked it already, which Vault refuses to do
+    // again
+    let lookup = PostLookupAccessorRequest {
+        accessor: accessor.clone(),
+    };
+    match vault.post_auth_token_lookup_accessor(&lookup).await {
+        Ok(_) => info!(phase, "Previous root token is still valid"),
+        Err(err) if err.is_invalid_accessor() => {
+            info!(phase, "Previous root token was already revoked");
             return Ok(());
         }
+        Err(vault::Error::Standby { location }) => {
+            let msg = "Vault stepped down to a standby node, so the previous root token cannot be \
+                       revoked until it is run against the active node";
+            error!(phase, active = location, msg);
+            bail!(msg);
+        }
+        Err(err) => {
+            error!(phase, "Failed looking up previous root token");
+            return Err(err.into());
+        }
     }
 
+    vault
+        .post_auth_token_revoke_accessor(&PostRevokeAccessorRequest { accessor })
+        .await
+        .inspect_err(|_| {
+            error!(phase, "Failed revoking previous root token");
+        })?;
+    info!(phase, "Successfully revoked previous root token");
+
     Ok(())
 }
 
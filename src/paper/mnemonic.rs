@@ -0,0 +1,159 @@
+use anyhow::Context;
+use bip39::Language;
+use sha2::Digest;
+use sha2::Sha256;
+
+const CHECKSUM_LEN: usize = 4;
+const BITS_PER_WORD: u32 = 11;
+
+/// Encodes a key share as words from the BIP-39 English word list, 11 bits per
+/// word. The share index and length are encoded ahead of the share and a
+/// checksum after it, so that transcription errors are caught when decoding.
+pub fn encode(index: u8, share: &[u8]) -> anyhow::Result<Vec<&'static str>> {
+    let words = Language::English.word_list();
+    let payload = payload(index, share)?;
+
+    let mut out = Vec::new();
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for byte in payload {
+        acc = (acc << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= BITS_PER_WORD {
+            bits -= BITS_PER_WORD;
+            out.push(words[(acc >> bits) as usize]);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    if bits > 0 {
+        out.push(words[(acc << (BITS_PER_WORD - bits)) as usize]);
+    }
+    Ok(out)
+}
+
+/// Decodes words produced by [`encode`] back into the share index and share.
+pub fn decode<S: AsRef<str>>(words: &[S]) -> anyhow::Result<(u8, Vec<u8>)> {
+    let mut bytes = Vec::new();
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for (i, word) in words.iter().enumerate() {
+        let word = word.as_ref().to_lowercase();
+        let value = Language::English
+            .find_word(&word)
+            .with_context(|| format!("Unknown word #{}: {word}", i + 1))?;
+        acc = (acc << BITS_PER_WORD) | u32::from(value);
+        bits += BITS_PER_WORD;
+        while bits >= 8 {
+            bits -= 8;
+            bytes.push(u8::try_from(acc >> bits)?);
+            acc &= (1 << bits) - 1;
+        }
+    }
+
+    let [index, len, rest @ ..] = bytes.as_slice() else {
+        anyhow::bail!("Too few words, check the words for typos");
+    };
+    let len = usize::from(*len);
+    if rest.len() < len + CHECKSUM_LEN {
+        anyhow::bail!("Too few words, check the words for typos");
+    }
+    let (share, rest) = rest.split_at(len);
+    let (checksum, padding) = rest.split_at(CHECKSUM_LEN);
+    if padding.len() > 1 || padding.iter().any(|b| *b != 0) {
+        anyhow::bail!("Too many words");
+    }
+
+    if payload(*index, share)?[2 + len..] != *checksum {
+        anyhow::bail!("Checksum mismatch, check the words for typos");
+    }
+    Ok((*index, share.to_vec()))
+}
+
+/// Checksum embedded in the encoded words, for comparing against by eye.
+pub fn checksum(index: u8, share: &[u8]) -> anyhow::Result<String> {
+    let payload = payload(index, share)?;
+    Ok(data_encoding::HEXLOWER.encode(&payload[payload.len() - CHECKSUM_LEN..]))
+}
+
+fn payload(index: u8, share: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let len = u8::try_from(share.len()).context("Share is too long to encode")?;
+    let mut payload = vec![index, len];
+    payload.extend_from_slice(share);
+    let digest = Sha256::digest(&payload);
+    payload.extend_from_slice(&digest[..CHECKSUM_LEN]);
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHARE: [u8; 33] = [
+        0x5a, 0x01, 0xff, 0x00, 0x80, 0x7f, 0x3c, 0xc3, 0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde,
+        0xf0, 0x0f, 0xed, 0xcb, 0xa9, 0x87, 0x65, 0x43, 0x21, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66,
+        0x77, 0x88, 0x99,
+    ];
+
+    #[test]
+    fn round_trips_shares() {
+        for len in [0, 1, 16, 32, 33] {
+            let words = encode(3, &SHARE[..len]).unwrap();
+            assert_eq!(decode(&words).unwrap(), (3, SHARE[..len].to_vec()));
+        }
+    }
+
+    #[test]
+    fn decodes_regardless_of_case() {
+        let words: Vec<String> = encode(1, &SHARE)
+            .unwrap()
+            .iter()
+            .map(|word| word.to_uppercase())
+            .collect();
+        assert_eq!(decode(&words).unwrap(), (1, SHARE.to_vec()));
+    }
+
+    #[test]
+    fn rejects_swapped_word() {
+        let mut words = encode(1, &SHARE).unwrap();
+        words.swap(3, 4);
+        let err = decode(&words).unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"), "{err}");
+    }
+
+    #[test]
+    fn rejects_unknown_word() {
+        let mut words = encode(1, &SHARE).unwrap();
+        words[2] = "vaultinit";
+        let err = decode(&words).unwrap_err();
+        assert!(err.to_string().contains("Unknown word #3"), "{err}");
+    }
+
+    #[test]
+    fn rejects_missing_and_extra_words() {
+        let words = encode(1, &SHARE).unwrap();
+        assert!(decode(&words[..words.len() - 1]).is_err());
+
+        let mut extra = words.clone();
+        extra.push("abandon");
+        extra.push("ability");
+        assert!(decode(&extra).is_err());
+    }
+
+    #[test]
+    fn checksum_matches_encoded_words() {
+        let checksum = checksum(2, &SHARE).unwrap();
+        assert_eq!(checksum.len(), CHECKSUM_LEN * 2);
+
+        let payload = payload(2, &SHARE).unwrap();
+        assert_eq!(
+            checksum,
+            data_encoding::HEXLOWER.encode(&payload[SHARE.len() + 2..])
+        );
+        assert_ne!(checksum, super::checksum(3, &SHARE).unwrap());
+    }
+
+    #[test]
+    fn rejects_share_too_long() {
+        assert!(encode(1, &[0; 256]).is_err());
+    }
+}
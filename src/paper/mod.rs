@@ -0,0 +1,113 @@
+pub mod mnemonic;
+pub mod pdf;
+
+use std::fmt::Write;
+
+use chrono::SecondsFormat;
+use qrcode::render::unicode::Dense1x2;
+use qrcode::QrCode;
+
+use crate::save::fingerprint;
+use crate::save::Envelope;
+
+const WORDS_PER_ROW: usize = 4;
+
+/// A single printed page, holding one key share.
+pub struct Page {
+    pub lines: Vec<String>,
+    /// QR code of the words, so they can be scanned rather than typed.
+    pub qr: QrCode,
+}
+
+impl Page {
+    pub fn to_text(&self) -> String {
+        let mut text = self.lines.join("\n");
+        text.push_str("\n\n");
+        text.push_str(&self.qr.render::<Dense1x2>().build());
+        text.push('\n');
+        text
+    }
+}
+
+/// Builds a page for each unseal key share, or each recovery key share if
+/// `recovery` is set.
+pub fn pages(envelope: &Envelope, recovery: bool) -> anyhow::Result<Vec<Page>> {
+    let data = envelope.data()?;
+    let (kind, keys, keys_base64) = if recovery {
+        (
+            "RECOVERY KEY",
+            &data.recovery_keys,
+            &data.recovery_keys_base64,
+        )
+    } else {
+        ("UNSEAL KEY", &data.keys, &data.keys_base64)
+    };
+    if keys_base64.is_empty() {
+        anyhow::bail!("Init data contains no {} shares", kind.to_lowercase());
+    }
+
+    let metadata = &envelope.metadata;
+    let total = keys_base64.len();
+    let mut pages = Vec::new();
+    for (i, share) in keys_base64.iter().enumerate() {
+        let index = u8::try_from(i)?;
+        let bytes = data_encoding::BASE64.decode(share.as_bytes())?;
+        let words = mnemonic::encode(index, &bytes)?;
+
+        let mut lines = vec![
+            format!("VAULT {kind} SHARE {} OF {total}", i + 1),
+            String::new(),
+            format!(
+                "Cluster name: {}",
+                metadata.cluster_name.as_deref().unwrap_or("-")
+            ),
+            format!(
+                "Cluster ID:   {}",
+                metadata.cluster_id.as_deref().unwrap_or("-")
+            ),
+            format!(
+                "Created:      {}",
+                metadata.created_at.map_or("-".to_owned(), |t| t
+                    .to_rfc3339_opts(SecondsFormat::Secs, true))
+            ),
+            format!(
+                "Threshold:    {} of {total} shares",
+                metadata
+                    .secret_threshold
+                    .map_or("-".to_owned(), |t| t.to_string())
+            ),
+            format!(
+                "Fingerprint:  {}",
+                keys.get(i).map_or("-".to_owned(), |key| fingerprint(key))
+            ),
+            format!("Checksum:     {}", mnemonic::checksum(index, &bytes)?),
+            String::new(),
+            format!("Words ({}):", words.len()),
+        ];
+        for (row, chunk) in words.chunks(WORDS_PER_ROW).enumerate() {
+            let mut line = String::new();
+            for (j, word) in chunk.iter().enumerate() {
+                let _ = write!(line, "{:>3}. {word:<10}", row * WORDS_PER_ROW + j + 1);
+            }
+            lines.push(line.trim_end().to_owned());
+        }
+        if !recovery {
+            lines.push(String::new());
+            lines.push("Unseal by typing the words into `vault-init import-paper`".to_owned());
+        }
+
+        let qr = QrCode::new(words.join(" "))?;
+        pages.push(Page { lines, qr });
+    }
+    Ok(pages)
+}
+
+/// Renders pages as plain text, separated by form feeds so that each prints on
+/// its own sheet.
+pub fn render_text(pages: &[Page]) -> String {
+    pages
+        .iter()
+        .map(Page::to_text)
+        .collect::<Vec<_>>()
+        .join("\x0c")
+}
@@ -0,0 +1,94 @@
+use std::fmt::Write;
+
+use qrcode::Color;
+
+use super::Page;
+
+// A4 in points
+const PAGE_WIDTH: usize = 595;
+const PAGE_HEIGHT: usize = 842;
+const MARGIN: usize = 50;
+const FONT_SIZE: usize = 10;
+const LEADING: usize = 14;
+const MODULE_SIZE: usize = 4;
+
+/// Renders pages as a minimal PDF using the standard Courier font, with QR
+/// codes drawn as filled squares. Only ASCII text is supported.
+pub fn render(pages: &[Page]) -> Vec<u8> {
+    // Objects 1-3 are the catalog, page tree and font, followed by a page and
+    // content stream object for each page
+    let kids: Vec<String> = (0..pages.len())
+        .map(|i| format!("{} 0 R", 4 + 2 * i))
+        .collect();
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_owned(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Courier >>".to_owned(),
+    ];
+    for (i, page) in pages.iter().enumerate() {
+        let content = content_stream(page);
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] \
+             /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            5 + 2 * i
+        ));
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{content}\nendstream",
+            content.len()
+        ));
+    }
+
+    let mut out = String::from("%PDF-1.4\n");
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        let _ = write!(out, "{} 0 obj\n{object}\nendobj\n", i + 1);
+    }
+    let xref = out.len();
+    let _ = write!(out, "xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        let _ = writeln!(out, "{offset:010} 00000 n ");
+    }
+    let _ = write!(
+        out,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+        objects.len() + 1
+    );
+    out.into_bytes()
+}
+
+fn content_stream(page: &Page) -> String {
+    let top = PAGE_HEIGHT - MARGIN;
+
+    let mut stream = format!("BT /F1 {FONT_SIZE} Tf {LEADING} TL {MARGIN} {top} Td\n");
+    for line in &page.lines {
+        let _ = writeln!(stream, "({}) Tj T*", escape(line));
+    }
+    stream.push_str("ET\n0 g\n");
+
+    let qr_top = top - page.lines.len() * LEADING - 2 * LEADING;
+    let width = page.qr.width();
+    for (i, color) in page.qr.to_colors().into_iter().enumerate() {
+        if color == Color::Dark {
+            let x = MARGIN + (i % width) * MODULE_SIZE;
+            let y = qr_top - (i / width + 1) * MODULE_SIZE;
+            let _ = writeln!(stream, "{x} {y} {MODULE_SIZE} {MODULE_SIZE} re");
+        }
+    }
+    stream.push('f');
+    stream
+}
+
+fn escape(line: &str) -> String {
+    line.chars()
+        .map(|c| match c {
+            '(' | ')' | '\\' => format!("\\{c}"),
+            c if c.is_ascii() && !c.is_ascii_control() => c.to_string(),
+            _ => "?".to_owned(),
+        })
+        .collect()
+}
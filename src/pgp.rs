@@ -0,0 +1,308 @@
+use anyhow::Context;
+use pgp::crypto::sym::SymmetricKeyAlgorithm;
+use pgp::errors::Error;
+use pgp::ser::Serialize as _;
+use pgp::types::PublicKeyTrait;
+use pgp::Deserializable;
+use pgp::Message;
+use pgp::SignedPublicKey;
+use pgp::SignedSecretKey;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::debug;
+use tracing::warn;
+
+use crate::config::KeySource;
+use crate::vault::models::sys::init::PostInitResponse;
+
+/// PGP private keys for decrypting key shares and root tokens that were
+/// encrypted at init with `--pgp-keys`, `--recovery-pgp-keys` or
+/// `--root-token-pgp-key`.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Pgp {
+    #[serde(default)]
+    pub private_key: Vec<PrivateKey>,
+}
+
+/// An ASCII-armored or binary PGP private key, optionally protected by a
+/// passphrase.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct PrivateKey {
+    #[serde(flatten)]
+    pub source: KeySource,
+    pub passphrase: Option<KeySource>,
+}
+
+/// Private keys that were read successfully, ready to decrypt with.
+#[derive(Default)]
+pub struct Decrypter {
+    keys: Vec<(SignedSecretKey, String)>,
+}
+
+impl Pgp {
+    /// Reads the configured private keys. Keys that cannot be read are skipped
+    /// with a warning, since key holders may not all be present.
+    pub async fn decrypter(&self) -> Decrypter {
+        let mut keys = Vec::new();
+        for (i, private_key) in self.private_key.iter().enumerate() {
+            match private_key.read().await {
+                Ok(key) => keys.push(key),
+                Err(err) => warn!(private_key = i, ?err, "Skipping unreadable PGP private key"),
+            }
+        }
+        Decrypter { keys }
+    }
+}
+
+impl PrivateKey {
+    async fn read(&self) -> anyhow::Result<(SignedSecretKey, String)> {
+        let bytes = self.source.read_binary().await?;
+        let key = if bytes.trim_ascii_start().starts_with(b"-----BEGIN") {
+            SignedSecretKey::from_string(std::str::from_utf8(bytes.trim_ascii())?)?.0
+        } else {
+            SignedSecretKey::from_bytes(bytes.as_slice())?
+        };
+        let passphrase = match &self.passphrase {
+            Some(passphrase) => String::from_utf8(passphrase.read().await?)?,
+            None => String::new(),
+        };
+        Ok((key, passphrase))
+    }
+}
+
+impl Decrypter {
+    /// The unseal keys, or recovery keys if `recovery` is set, in a form that
+    /// can be submitted to Vault. Keys encrypted with PGP are decrypted, and
+    /// those that none of the private keys can decrypt are left out.
+    pub fn keys(&self, data: &PostInitResponse, recovery: bool) -> anyhow::Result<Vec<String>> {
+        let (keys, keys_base64) = if recovery {
+            (&data.recovery_keys, &data.recovery_keys_base64)
+        } else {
+            (&data.keys, &data.keys_base64)
+        };
+
+        let mut decrypted = Vec::new();
+        for (i, key) in keys.iter().enumerate() {
+            let Some(message) = keys_base64.get(i).and_then(|b64| message(b64)) else {
+                decrypted.push(key.clone());
+                continue;
+            };
+            if let Some(key) = self.decrypt(&message)? {
+                decrypted.push(key);
+            } else {
+                debug!(key = i, "No PGP private key can decrypt key share");
+            }
+        }
+        if decrypted.len() < keys.len() {
+            warn!(
+                "Left out {} key shares that no PGP private key can decrypt",
+                keys.len() - decrypted.len()
+            );
+        }
+        Ok(decrypted)
+    }
+
+    /// The root token, decrypted if it was encrypted with PGP. `None` if none
+    /// of the private keys can decrypt it.
+    pub fn root_token(&self, root_token: &str) -> anyhow::Result<Option<String>> {
+        match message(root_token) {
+            Some(message) => self.decrypt(&message),
+            None => Ok(Some(root_token.to_owned())),
+        }
+    }
+
+    /// Decrypts a message with whichever private key it was encrypted to.
+    /// `None` if it was encrypted to none of them, and an error if it was but
+    /// none could decrypt it.
+    fn decrypt(&self, message: &Message) -> anyhow::Result<Option<String>> {
+        let mut failed = None;
+        for (i, (key, passphrase)) in self.keys.iter().enumerate() {
+            let decrypted = match message.decrypt(|| passphrase.clone(), &[key]) {
+                Ok((decrypted, _)) => decrypted.get_content(),
+                Err(err) => Err(err),
+            };
+            match decrypted {
+                Ok(Some(content)) => {
+                    return Ok(Some(String::from_utf8(content)?.trim().to_owned()))
+                }
+                Ok(None) => {
+                    warn!(private_key = i, "Decrypted PGP message has no content");
+                    failed = Some(anyhow::anyhow!("Decrypted PGP message has no content"));
+                }
+                Err(Error::MissingKey) => {}
+                Err(err) => {
+                    warn!(private_key = i, %err, "Failed decrypting with PGP private key");
+                    failed = Some(err.into());
+                }
+            }
+        }
+        failed.map_or(Ok(None), Err)
+    }
+}
+
+/// Encrypts `plaintext` to a base64-encoded PGP public key, as Vault does
+/// with `--root-token-pgp-key`, returning a base64-encoded PGP message.
+pub fn encrypt(public_key: &str, plaintext: &str) -> anyhow::Result<String> {
+    let bytes = data_encoding::BASE64
+        .decode(public_key.trim().as_bytes())
+        .context("PGP public key must be base64-encoded, keybase is not supported")?;
+    let key = SignedPublicKey::from_bytes(bytes.as_slice())?;
+    let encrypted = encrypt_to(&key, plaintext.as_bytes())?;
+    Ok(data_encoding::BASE64.encode(&encrypted.to_bytes()?))
+}
+
+/// Encrypts `data` to the encryption subkey of the public key, or the primary
+/// key if it has none.
+pub fn encrypt_to(key: &SignedPublicKey, data: &[u8]) -> anyhow::Result<Message> {
+    let message = Message::new_literal_bytes("", data);
+    let mut rng = rand::thread_rng();
+    let algorithm = SymmetricKeyAlgorithm::AES256;
+    let encrypted = if let Some(subkey) = key
+        .public_subkeys
+        .iter()
+        .find(|subkey| PublicKeyTrait::is_encryption_key(*subkey))
+    {
+        message.encrypt_to_keys_seipdv1(&mut rng, algorithm, &[subkey])?
+    } else if key.is_encryption_key() {
+        message.encrypt_to_keys_seipdv1(&mut rng, algorithm, &[key])?
+    } else {
+        anyhow::bail!("PGP public key cannot encrypt");
+    };
+    Ok(encrypted)
+}
+
+/// Parses a base64-encoded value as an encrypted PGP message, or `None` if
+/// it is not one, ie it was stored in the clear.
+fn message(value: &str) -> Option<Message> {
+    let bytes = data_encoding::BASE64.decode(value.as_bytes()).ok()?;
+    match Message::from_bytes(bytes.as_slice()) {
+        Ok(message @ Message::Encrypted { .. }) => Some(message),
+        _ => None,
+    }
+}
+
+/// New private key protected by `passphrase`, and its base64-encoded public
+/// key, for tests.
+#[cfg(test)]
+pub(crate) fn key_pair(passphrase: &str) -> (SignedSecretKey, String) {
+    use pgp::composed::KeyType;
+    use pgp::composed::SecretKeyParamsBuilder;
+    use pgp::composed::SubkeyParamsBuilder;
+    use pgp::crypto::ecc_curve::ECCCurve;
+    use pgp::types::SecretKeyTrait;
+
+    let passphrase = (!passphrase.is_empty()).then(|| passphrase.to_owned());
+    let params = SecretKeyParamsBuilder::default()
+        .key_type(KeyType::EdDSALegacy)
+        .can_certify(true)
+        .can_sign(true)
+        .primary_user_id("vault-init <test@example.com>".into())
+        .passphrase(passphrase.clone())
+        .subkey(
+            SubkeyParamsBuilder::default()
+                .key_type(KeyType::ECDH(ECCCurve::Curve25519))
+                .can_encrypt(true)
+                .passphrase(passphrase.clone())
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    let mut rng = rand::thread_rng();
+    let password = || passphrase.clone().unwrap_or_default();
+    let key = params
+        .generate(&mut rng)
+        .unwrap()
+        .sign(&mut rng, password)
+        .unwrap();
+    let public_key = key.public_key().sign(&mut rng, &key, password).unwrap();
+    let public_key = data_encoding::BASE64.encode(&public_key.to_bytes().unwrap());
+    (key, public_key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decrypts_with_matching_key() {
+        let (key, public_key) = key_pair("");
+        let (other, _) = key_pair("");
+        let encrypted = encrypt(&public_key, "hvs.root").unwrap();
+
+        let decrypter = Decrypter {
+            keys: vec![(other, String::new()), (key, String::new())],
+        };
+        assert_eq!(
+            decrypter.root_token(&encrypted).unwrap().as_deref(),
+            Some("hvs.root")
+        );
+    }
+
+    #[test]
+    fn leaves_plaintext_as_is() {
+        let decrypter = Decrypter::default();
+        assert_eq!(
+            decrypter.root_token("hvs.root").unwrap().as_deref(),
+            Some("hvs.root")
+        );
+    }
+
+    #[test]
+    fn returns_none_without_matching_key() {
+        let (_, public_key) = key_pair("");
+        let (other, _) = key_pair("");
+        let encrypted = encrypt(&public_key, "hvs.root").unwrap();
+
+        let decrypter = Decrypter {
+            keys: vec![(other, String::new())],
+        };
+        assert_eq!(decrypter.root_token(&encrypted).unwrap(), None);
+    }
+
+    #[test]
+    fn tries_next_key_after_failure() {
+        let (key, public_key) = key_pair("secret");
+        let encrypted = encrypt(&public_key, "hvs.root").unwrap();
+
+        let decrypter = Decrypter {
+            keys: vec![(key.clone(), "wrong".to_owned())],
+        };
+        assert!(decrypter.root_token(&encrypted).is_err());
+
+        let decrypter = Decrypter {
+            keys: vec![
+                (key.clone(), "wrong".to_owned()),
+                (key, "secret".to_owned()),
+            ],
+        };
+        assert_eq!(
+            decrypter.root_token(&encrypted).unwrap().as_deref(),
+            Some("hvs.root")
+        );
+    }
+
+    #[test]
+    fn leaves_out_key_shares_it_cannot_decrypt() {
+        let (key, public_key) = key_pair("");
+        let (_, other_public_key) = key_pair("");
+        let data = PostInitResponse {
+            keys: vec!["a".to_owned(), "b".to_owned()],
+            keys_base64: vec![
+                encrypt(&public_key, "aa").unwrap(),
+                encrypt(&other_public_key, "bb").unwrap(),
+            ],
+            ..Default::default()
+        };
+
+        let decrypter = Decrypter {
+            keys: vec![(key, String::new())],
+        };
+        assert_eq!(decrypter.keys(&data, false).unwrap(), ["aa"]);
+    }
+
+    #[test]
+    fn rejects_keybase_public_key() {
+        assert!(encrypt("keybase:someone", "hvs.root").is_err());
+    }
+}
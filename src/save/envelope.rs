@@ -0,0 +1,313 @@
+use chrono::DateTime;
+use chrono::Utc;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Digest;
+use sha2::Sha256;
+
+use super::integrity::Signature;
+use super::route::Shares;
+use super::split::Part;
+use crate::vault::models::sys::init::PostInitResponse;
+use crate::vault::models::sys::seal_status::GetSealStatusResponse;
+
+/// Schema version written by this build. Version 0 denotes the legacy format,
+/// which was a bare `PostInitResponse` with no metadata.
+pub const ENVELOPE_VERSION: u32 = 1;
+
+/// Versioned container for init data, carrying metadata that makes the stored
+/// payload self-describing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub version: u32,
+    pub metadata: Metadata,
+    #[serde(flatten)]
+    pub payload: Payload,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<Signature>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub cluster_id: Option<String>,
+    pub cluster_name: Option<String>,
+    pub vault_version: Option<String>,
+    pub seal_type: Option<String>,
+    pub secret_shares: Option<i64>,
+    pub secret_threshold: Option<i64>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub rotated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub key_fingerprints: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recovery_key_fingerprints: Vec<String>,
+    /// SHA-256 pin of the certificate Vault presented, if reached over HTTPS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_pin_sha256: Option<String>,
+}
+
+/// What an envelope holds, stored under the `data`, `part`, `shares` or
+/// `root_token` key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Payload {
+    /// Complete init data.
+    Data(PostInitResponse),
+    /// One part of init data that was split across save methods.
+    Part(Part),
+    /// Some of the key shares, routed to one save method.
+    Shares(Shares),
+    /// Only the root token, routed to one save method.
+    RootToken(String),
+}
+
+/// Either of the formats that may be found in a save method.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Stored {
+    Envelope(Box<Envelope>),
+    Legacy(PostInitResponse),
+}
+
+impl Envelope {
+    /// Wraps freshly created init data in an envelope of the current version.
+    pub fn new(data: PostInitResponse) -> Self {
+        let mut envelope = Self {
+            version: ENVELOPE_VERSION,
+            metadata: Metadata {
+                created_at: Some(Utc::now()),
+                ..Default::default()
+            },
+            payload: Payload::Data(data),
+            signature: None,
+        };
+        envelope.update_fingerprints();
+        envelope
+    }
+
+    /// Parses stored init data, accepting both the envelope and the legacy bare
+    /// format.
+    pub fn from_slice(bytes: &[u8]) -> anyhow::Result<Self> {
+        let envelope = match serde_json::from_slice(bytes)? {
+            Stored::Envelope(envelope) => *envelope,
+            Stored::Legacy(data) => Self {
+                version: 0,
+                metadata: Metadata::default(),
+                payload: Payload::Data(data),
+                signature: None,
+            },
+        };
+        if envelope.version > ENVELOPE_VERSION {
+            anyhow::bail!(
+                "Unsupported init data schema version {} (newest supported is {ENVELOPE_VERSION})",
+                envelope.version
+            );
+        }
+        Ok(envelope)
+    }
+
+    /// The complete init data, failing if the envelope only holds a part.
+    pub fn data(&self) -> anyhow::Result<&PostInitResponse> {
+        match &self.payload {
+            Payload::Data(data) => Ok(data),
+            Payload::Part(_) => Err(anyhow::anyhow!("Envelope holds only part of init data")),
+            Payload::Shares(shares) if shares.is_complete() => Err(anyhow::anyhow!(
+                "Envelope holds key shares, but not the root token"
+            )),
+            Payload::Shares(shares) => Err(anyhow::anyhow!(
+                "Envelope holds only {} of the {} key shares",
+                shares.indexes.len(),
+                shares.total
+            )),
+            Payload::RootToken(_) => Err(anyhow::anyhow!("Envelope holds only the root token")),
+        }
+    }
+
+    /// The root token, failing if the envelope holds only key shares or a
+    /// part.
+    pub fn root_token(&self) -> anyhow::Result<&str> {
+        match &self.payload {
+            Payload::Data(data) => Ok(&data.root_token),
+            Payload::Shares(shares) if !shares.data.root_token.is_empty() => {
+                Ok(&shares.data.root_token)
+            }
+            Payload::RootToken(root_token) => Ok(root_token),
+            Payload::Shares(_) => Err(anyhow::anyhow!("Envelope holds only key shares")),
+            Payload::Part(_) => Err(anyhow::anyhow!("Envelope holds only part of init data")),
+        }
+    }
+
+    /// Init data holding the key shares available, which may be fewer than all
+    /// of them if they were routed to different save methods.
+    pub fn key_shares(&self) -> anyhow::Result<&PostInitResponse> {
+        match &self.payload {
+            Payload::Shares(shares) => Ok(&shares.data),
+            _ => self.data(),
+        }
+    }
+
+    pub fn is_legacy(&self) -> bool {
+        self.version < ENVELOPE_VERSION
+    }
+
+    /// Brings the envelope to the current version, filling in metadata from
+    /// the seal status of the running Vault.
+    pub fn upgrade(&mut self, seal_status: &GetSealStatusResponse) {
+        self.version = ENVELOPE_VERSION;
+        self.metadata.update(seal_status);
+        self.update_fingerprints();
+    }
+
+    fn update_fingerprints(&mut self) {
+        let Payload::Data(data) = &self.payload else {
+            return;
+        };
+        self.metadata.key_fingerprints = data.keys.iter().map(|k| fingerprint(k)).collect();
+        self.metadata.recovery_key_fingerprints =
+            data.recovery_keys.iter().map(|k| fingerprint(k)).collect();
+    }
+
+    /// Replaces the root token after a successful rotation.
+    pub fn rotate_root_token(&mut self, root_token: String) -> anyhow::Result<()> {
+        let Payload::Data(data) = &mut self.payload else {
+            anyhow::bail!("Envelope holds only part of init data");
+        };
+        data.root_token = root_token;
+        self.metadata.rotated_at = Some(Utc::now());
+        Ok(())
+    }
+}
+
+impl Metadata {
+    pub fn update(&mut self, seal_status: &GetSealStatusResponse) {
+        // The cluster ID identifies the server init data belongs to, so it is
+        // never replaced once recorded
+        if self.cluster_id.is_none() {
+            self.cluster_id.clone_from(&seal_status.cluster_id);
+        }
+        if seal_status.cluster_name.is_some() {
+            self.cluster_name.clone_from(&seal_status.cluster_name);
+        }
+        self.vault_version = Some(seal_status.version.clone());
+        self.seal_type = Some(seal_status.r#type.clone());
+        self.secret_shares = Some(seal_status.n);
+        self.secret_threshold = Some(seal_status.t);
+    }
+
+    /// Whether two envelopes may hold data from the same init, judging by the
+    /// cluster ID and key fingerprints where both record them.
+    pub fn same_init(&self, other: &Metadata) -> bool {
+        let differ = |a: &[String], b: &[String]| !a.is_empty() && !b.is_empty() && a != b;
+        let cluster_differs = matches!(
+            (&self.cluster_id, &other.cluster_id),
+            (Some(a), Some(b)) if a != b
+        );
+        !cluster_differs
+            && !differ(&self.key_fingerprints, &other.key_fingerprints)
+            && !differ(
+                &self.recovery_key_fingerprints,
+                &other.recovery_key_fingerprints,
+            )
+    }
+}
+
+/// Short, non-reversible identifier for a secret value, suitable for logs.
+pub fn fingerprint(secret: &str) -> String {
+    let digest = Sha256::digest(secret.as_bytes());
+    format!("sha256:{}", data_encoding::HEXLOWER.encode(&digest[..8]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn init_data() -> PostInitResponse {
+        PostInitResponse {
+            keys: vec!["aa".to_owned(), "bb".to_owned()],
+            keys_base64: vec!["qg==".to_owned(), "uw==".to_owned()],
+            root_token: "hvs.root".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_legacy_format() {
+        let legacy = serde_json::to_vec(&init_data()).unwrap();
+        let envelope = Envelope::from_slice(&legacy).unwrap();
+        assert_eq!(envelope.version, 0);
+        assert!(envelope.is_legacy());
+        assert_eq!(envelope.metadata, Metadata::default());
+        assert_eq!(envelope.data().unwrap(), &init_data());
+    }
+
+    #[test]
+    fn round_trips_envelope() {
+        let envelope = Envelope::new(init_data());
+        let parsed = Envelope::from_slice(&serde_json::to_vec(&envelope).unwrap()).unwrap();
+        assert_eq!(parsed, envelope);
+        assert!(!parsed.is_legacy());
+        assert_eq!(parsed.metadata.key_fingerprints.len(), 2);
+    }
+
+    #[test]
+    fn rejects_newer_version() {
+        let mut envelope = Envelope::new(init_data());
+        envelope.version = ENVELOPE_VERSION + 1;
+        let bytes = serde_json::to_vec(&envelope).unwrap();
+        assert!(Envelope::from_slice(&bytes).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_format() {
+        assert!(Envelope::from_slice(br#"{"foo":"bar"}"#).is_err());
+    }
+
+    #[test]
+    fn reads_root_token_only() {
+        let mut envelope = Envelope::new(init_data());
+        envelope.payload = Payload::RootToken("hvs.other".to_owned());
+        assert_eq!(envelope.root_token().unwrap(), "hvs.other");
+        assert!(envelope.data().is_err());
+    }
+
+    #[test]
+    fn upgrades_legacy_format() {
+        let legacy = serde_json::to_vec(&init_data()).unwrap();
+        let mut envelope = Envelope::from_slice(&legacy).unwrap();
+        let seal_status = GetSealStatusResponse {
+            r#type: "shamir".to_owned(),
+            t: 2,
+            n: 2,
+            cluster_id: Some("cluster".to_owned()),
+            ..Default::default()
+        };
+        envelope.upgrade(&seal_status);
+        assert_eq!(envelope.version, ENVELOPE_VERSION);
+        assert_eq!(envelope.metadata.cluster_id.as_deref(), Some("cluster"));
+        assert_eq!(envelope.metadata.secret_threshold, Some(2));
+        assert_eq!(
+            envelope.metadata.key_fingerprints,
+            vec![fingerprint("aa"), fingerprint("bb")]
+        );
+    }
+
+    #[test]
+    fn tells_apart_inits() {
+        let envelope = Envelope::new(init_data());
+        assert!(envelope.metadata.same_init(&envelope.metadata));
+        assert!(envelope.metadata.same_init(&Metadata::default()));
+
+        let other = Envelope::new(PostInitResponse {
+            keys: vec!["cc".to_owned(), "dd".to_owned()],
+            ..init_data()
+        });
+        assert!(!envelope.metadata.same_init(&other.metadata));
+
+        let mut cluster = envelope.metadata.clone();
+        cluster.cluster_id = Some("a".to_owned());
+        let mut other_cluster = envelope.metadata.clone();
+        other_cluster.cluster_id = Some("b".to_owned());
+        assert!(cluster.same_init(&envelope.metadata));
+        assert!(!cluster.same_init(&other_cluster));
+    }
+}
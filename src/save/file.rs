@@ -1,30 +1,87 @@
+use std::ffi::OsString;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
 use std::path::PathBuf;
 
+use anyhow::Context;
 use serde::Deserialize;
 use serde::Serialize;
 use tracing::debug;
 use tracing::warn;
 
+use super::Backend;
+use super::Capabilities;
+use super::Envelope;
 use super::Load;
+use super::NotFound;
 use super::Save;
-use crate::vault::models::sys::init::PostInitResponse;
+use super::Version;
+use super::Versioned;
 
 const DEFAULT_PATH: &str = "vault-init.json";
+const DEFAULT_MODE: u32 = 0o600;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct File {
     pub path: Option<PathBuf>,
     pub overwrite: Option<bool>,
+    /// Permission bits of the written file as an octal string, ie `"0600"`.
+    pub mode: Option<String>,
+    /// Numeric user ID to own the written file.
+    pub owner: Option<u32>,
+    /// Numeric group ID to own the written file.
+    pub group: Option<u32>,
+    /// Keep the previous file as `<path>.bak` when overwriting.
+    pub backup: Option<bool>,
+    /// Number of previous versions to retain as `<path>.1`, `<path>.2`, ...
+    /// when overwriting.
+    pub history: Option<usize>,
 }
 
-#[async_trait::async_trait]
-impl Save for File {
-    async fn save_init(&self, data: &PostInitResponse) -> anyhow::Result<()> {
-        debug!(save_method = "file", "Saving init data");
-        let path = self.path.clone().unwrap_or(PathBuf::from(DEFAULT_PATH));
+impl File {
+    fn path(&self) -> PathBuf {
+        self.path.clone().unwrap_or(PathBuf::from(DEFAULT_PATH))
+    }
+
+    /// Path holding the given version, where version 0 is the current data.
+    fn version_path(&self, version: usize) -> PathBuf {
+        match version {
+            0 => self.path(),
+            n => sibling(&self.path(), &format!(".{n}")),
+        }
+    }
 
-        let metadata = tokio::fs::metadata(&path).await?;
-        if metadata.is_file() {
+    fn mode(&self) -> anyhow::Result<u32> {
+        let Some(mode) = &self.mode else {
+            return Ok(DEFAULT_MODE);
+        };
+        let digits = mode.trim_start_matches("0o");
+        u32::from_str_radix(digits, 8).with_context(|| format!("Invalid file mode: {mode}"))
+    }
+
+    /// Writes `contents` to the configured path by way of a temporary file in
+    /// the same directory, which is synced to disk and then renamed over the
+    /// destination. An advisory lock is held for the duration of the write so
+    /// that concurrent writers fail instead of interleaving.
+    fn write_atomic(&self, contents: &[u8]) -> anyhow::Result<()> {
+        let path = self.path();
+        let mode = self.mode()?;
+
+        let lock_path = sibling(&path, ".lock");
+        let lock = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(mode)
+            .open(&lock_path)
+            .with_context(|| format!("Failed opening lock file: {}", lock_path.display()))?;
+        lock.try_lock()
+            .context("File is locked by another writer")?;
+
+        if path.try_exists()? {
             if !self.overwrite.unwrap_or(false) {
                 return Err(anyhow::anyhow!(
                     "File already exists, but not configured to overwrite"
@@ -34,23 +91,345 @@ impl Save for File {
             warn!(
                 save_method = "file",
                 path = &path.to_string_lossy().to_string(),
-                "Existing secret found, overwriting"
+                "Existing file found, overwriting"
             );
+
+            if self.backup.unwrap_or(false) {
+                let backup_path = sibling(&path, ".bak");
+                debug!(
+                    save_method = "file",
+                    path = &backup_path.to_string_lossy().to_string(),
+                    "Backing up existing file"
+                );
+                std::fs::copy(&path, &backup_path)?;
+                std::fs::set_permissions(&backup_path, PermissionsExt::from_mode(mode))?;
+                if self.owner.is_some() || self.group.is_some() {
+                    std::os::unix::fs::chown(&backup_path, self.owner, self.group)?;
+                }
+            }
+
+            self.rotate_history()?;
         }
 
-        let contents = serde_json::to_vec(data)?;
-        tokio::fs::write(path, &contents).await?;
+        // The PID is the same after a container restarts, so a temporary file
+        // left by a crashed writer is removed while the lock is held
+        let tmp_path = sibling(&path, &format!(".tmp.{}", std::process::id()));
+        if tmp_path.try_exists()? {
+            warn!(
+                save_method = "file",
+                path = &tmp_path.to_string_lossy().to_string(),
+                "Removing temporary file left by an interrupted write"
+            );
+            std::fs::remove_file(&tmp_path)?;
+        }
+        let result = self.write_tmp(&tmp_path, mode, contents).and_then(|()| {
+            std::fs::rename(&tmp_path, &path)?;
+            sync_parent(&path)
+        });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+
+        lock.unlock()?;
+        result
+    }
+
+    /// Shifts the retained versions back by one, dropping any beyond the
+    /// configured history. The current file is hard linked as version 1, so it
+    /// remains in place until the rename replaces it.
+    fn rotate_history(&self) -> anyhow::Result<()> {
+        let history = self.history.unwrap_or(0);
+        if history == 0 {
+            return Ok(());
+        }
+
+        for version in (1..history).rev() {
+            let from = self.version_path(version);
+            if from.try_exists()? {
+                std::fs::rename(from, self.version_path(version + 1))?;
+            }
+        }
+        let first = self.version_path(1);
+        if first.try_exists()? {
+            std::fs::remove_file(&first)?;
+        }
+        std::fs::hard_link(self.path(), first)?;
         Ok(())
     }
+
+    fn write_tmp(&self, tmp_path: &Path, mode: u32, contents: &[u8]) -> anyhow::Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(mode)
+            .open(tmp_path)?;
+
+        // The mode given at creation is subject to the umask, so set it again
+        file.set_permissions(PermissionsExt::from_mode(mode))?;
+        if self.owner.is_some() || self.group.is_some() {
+            std::os::unix::fs::fchown(&file, self.owner, self.group)?;
+        }
+
+        file.write_all(contents)?;
+        file.sync_all()?;
+        Ok(())
+    }
+}
+
+/// Path in the same directory as `path`, with `suffix` appended to the name.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Syncs the directory containing `path` so that a rename survives a crash.
+pub(crate) fn sync_parent(path: &Path) -> anyhow::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    std::fs::File::open(parent)?.sync_all()?;
+    Ok(())
+}
+
+impl Backend for File {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            versions: self.history.unwrap_or(0) > 0,
+            cas: false,
+            secret_safe: false,
+        }
+    }
+
+    fn versioned(&self) -> Option<&dyn Versioned> {
+        self.capabilities()
+            .versions
+            .then_some(self as &dyn Versioned)
+    }
+}
+
+#[async_trait::async_trait]
+impl Save for File {
+    async fn save_init(&self, data: &Envelope) -> anyhow::Result<()> {
+        debug!(save_method = "file", "Saving init data");
+        let contents = serde_json::to_vec(data)?;
+        let file = self.clone();
+        tokio::task::spawn_blocking(move || file.write_atomic(&contents)).await?
+    }
 }
 
 #[async_trait::async_trait]
 impl Load for File {
-    async fn load_init(&self) -> anyhow::Result<PostInitResponse> {
+    async fn load_init(&self) -> anyhow::Result<Envelope> {
         debug!(save_method = "file", "Loading init data");
-        let path = self.path.clone().unwrap_or(PathBuf::from(DEFAULT_PATH));
-        let contents = tokio::fs::read(path).await?;
-        let data: PostInitResponse = serde_json::from_slice(&contents)?;
-        Ok(data)
+        self.load_version(0).await
+    }
+}
+
+#[async_trait::async_trait]
+impl Versioned for File {
+    async fn list_versions(&self) -> anyhow::Result<Vec<Version>> {
+        let mut versions = Vec::new();
+        for version in 0..=self.history.unwrap_or(0) {
+            if tokio::fs::try_exists(self.version_path(version)).await? {
+                let envelope = self.load_version(version).await?;
+                versions.push(Version { version, envelope });
+            }
+        }
+        Ok(versions)
+    }
+
+    async fn load_version(&self, version: usize) -> anyhow::Result<Envelope> {
+        let contents = match tokio::fs::read(self.version_path(version)).await {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Err(NotFound.into()),
+            Err(err) => return Err(err.into()),
+        };
+        Envelope::from_slice(&contents)
+    }
+
+    async fn restore(&self, data: &Envelope) -> anyhow::Result<()> {
+        let file = File {
+            overwrite: Some(true),
+            ..self.clone()
+        };
+        file.save_init(data).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::models::sys::init::PostInitResponse;
+
+    fn init_data(root_token: &str) -> Envelope {
+        Envelope::new(PostInitResponse {
+            keys: vec!["aa".to_owned()],
+            keys_base64: vec!["qg==".to_owned()],
+            root_token: root_token.to_owned(),
+            ..Default::default()
+        })
+    }
+
+    /// Empty directory for the files of one test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("vault-init-file-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn file(dir: &Path) -> File {
+        File {
+            path: Some(dir.join("init.json")),
+            overwrite: None,
+            mode: None,
+            owner: None,
+            group: None,
+            backup: None,
+            history: None,
+        }
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    async fn root_token(file: &File, version: usize) -> String {
+        let envelope = file.load_version(version).await.unwrap();
+        envelope.root_token().unwrap().to_owned()
+    }
+
+    #[tokio::test]
+    async fn writes_without_leaving_temporary_files() {
+        let dir = test_dir("atomic");
+        let file = file(&dir);
+        file.save_init(&init_data("hvs.a")).await.unwrap();
+
+        assert_eq!(file_names(&dir), ["init.json", "init.json.lock"]);
+        assert_eq!(root_token(&file, 0).await, "hvs.a");
+    }
+
+    #[tokio::test]
+    async fn removes_temporary_file_of_interrupted_write() {
+        let dir = test_dir("stale-tmp");
+        let file = file(&dir);
+        let tmp = dir.join(format!("init.json.tmp.{}", std::process::id()));
+        std::fs::write(&tmp, "partial").unwrap();
+        file.save_init(&init_data("hvs.a")).await.unwrap();
+
+        assert!(!tmp.exists());
+        assert_eq!(root_token(&file, 0).await, "hvs.a");
+    }
+
+    #[tokio::test]
+    async fn sets_mode() {
+        let dir = test_dir("mode");
+        let mode = |path: PathBuf| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        file(&dir).save_init(&init_data("hvs.a")).await.unwrap();
+        assert_eq!(mode(dir.join("init.json")), 0o600);
+
+        let file = File {
+            path: Some(dir.join("other.json")),
+            mode: Some("0640".to_owned()),
+            ..file(&dir)
+        };
+        file.save_init(&init_data("hvs.a")).await.unwrap();
+        assert_eq!(mode(dir.join("other.json")), 0o640);
+
+        let invalid = File {
+            mode: Some("rw".to_owned()),
+            ..file
+        };
+        assert!(invalid.save_init(&init_data("hvs.a")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn keeps_backup_of_previous_file() {
+        let dir = test_dir("backup");
+        let file = File {
+            overwrite: Some(true),
+            backup: Some(true),
+            ..file(&dir)
+        };
+        file.save_init(&init_data("hvs.a")).await.unwrap();
+        assert!(!dir.join("init.json.bak").exists());
+
+        file.save_init(&init_data("hvs.b")).await.unwrap();
+        let backup = std::fs::read(dir.join("init.json.bak")).unwrap();
+        let backup = Envelope::from_slice(&backup).unwrap();
+        assert_eq!(backup.root_token().unwrap(), "hvs.a");
+        assert_eq!(root_token(&file, 0).await, "hvs.b");
+    }
+
+    #[tokio::test]
+    async fn refuses_overwriting_unless_configured() {
+        let dir = test_dir("overwrite");
+        let file = file(&dir);
+        file.save_init(&init_data("hvs.a")).await.unwrap();
+
+        assert!(file.save_init(&init_data("hvs.b")).await.is_err());
+        assert_eq!(root_token(&file, 0).await, "hvs.a");
+        assert_eq!(file_names(&dir), ["init.json", "init.json.lock"]);
+    }
+
+    #[tokio::test]
+    async fn retains_versions_up_to_history() {
+        let dir = test_dir("history");
+        let file = File {
+            overwrite: Some(true),
+            history: Some(2),
+            ..file(&dir)
+        };
+        for root_token in ["hvs.a", "hvs.b", "hvs.c", "hvs.d"] {
+            file.save_init(&init_data(root_token)).await.unwrap();
+        }
+
+        assert_eq!(root_token(&file, 0).await, "hvs.d");
+        assert_eq!(root_token(&file, 1).await, "hvs.c");
+        assert_eq!(root_token(&file, 2).await, "hvs.b");
+        assert!(!dir.join("init.json.3").exists());
+
+        let versions = file.list_versions().await.unwrap();
+        let listed: Vec<_> = versions.iter().map(|version| version.version).collect();
+        assert_eq!(listed, [0, 1, 2]);
+        assert!(file
+            .load_version(3)
+            .await
+            .unwrap_err()
+            .downcast_ref::<NotFound>()
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn restores_version_retaining_replaced_data() {
+        let dir = test_dir("restore");
+        let file = File {
+            history: Some(2),
+            ..file(&dir)
+        };
+        file.save_init(&init_data("hvs.a")).await.unwrap();
+        File {
+            overwrite: Some(true),
+            ..file.clone()
+        }
+        .save_init(&init_data("hvs.b"))
+        .await
+        .unwrap();
+
+        // Restoring overwrites though the save method is not configured to
+        let previous = file.load_version(1).await.unwrap();
+        file.restore(&previous).await.unwrap();
+        assert_eq!(root_token(&file, 0).await, "hvs.a");
+        assert_eq!(root_token(&file, 1).await, "hvs.b");
+        assert_eq!(root_token(&file, 2).await, "hvs.a");
     }
 }
@@ -0,0 +1,293 @@
+use anyhow::Context;
+use ed25519_dalek::pkcs8::DecodePrivateKey;
+use ed25519_dalek::pkcs8::DecodePublicKey;
+use ed25519_dalek::Signer;
+use ed25519_dalek::SigningKey;
+use ed25519_dalek::VerifyingKey;
+use hmac::KeyInit;
+use hmac::Mac;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Sha256;
+use tracing::warn;
+
+use super::Envelope;
+use crate::config::KeySource;
+
+const HMAC_SHA256: &str = "hmac-sha256";
+const ED25519: &str = "ed25519";
+
+/// Integrity protection for stored init data. Exactly one of `hmac_key` or
+/// the Ed25519 keys should be configured.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Integrity {
+    /// Shared key used to compute an HMAC-SHA256 over the envelope.
+    pub hmac_key: Option<KeySource>,
+    /// PKCS#8 PEM Ed25519 private key used to sign the envelope when saving.
+    pub ed25519_private_key: Option<KeySource>,
+    /// PEM Ed25519 public key used to verify the envelope when loading.
+    /// Derived from the private key if not set.
+    pub ed25519_public_key: Option<KeySource>,
+    /// Accept unsigned init data when loading, ie while migrating existing
+    /// data. Signed data is still verified.
+    pub allow_unsigned: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Signature {
+    pub algorithm: String,
+    /// Base64-encoded MAC or signature over the envelope without this field.
+    pub value: String,
+}
+
+impl Integrity {
+    /// Signs the envelope in place, replacing any existing signature.
+    pub async fn sign(&self, envelope: &mut Envelope) -> anyhow::Result<()> {
+        let message = signed_bytes(envelope)?;
+
+        let (algorithm, value) = if let Some(hmac_key) = &self.hmac_key {
+            let mut mac = hmac::Hmac::<Sha256>::new_from_slice(&hmac_key.read().await?)?;
+            mac.update(&message);
+            (HMAC_SHA256, mac.finalize().into_bytes().to_vec())
+        } else if let Some(private_key) = &self.ed25519_private_key {
+            let signing_key = signing_key(private_key).await?;
+            (ED25519, signing_key.sign(&message).to_bytes().to_vec())
+        } else {
+            anyhow::bail!("Integrity protection is enabled, but no signing key is configured");
+        };
+
+        envelope.signature = Some(Signature {
+            algorithm: algorithm.to_owned(),
+            value: data_encoding::BASE64.encode(&value),
+        });
+        Ok(())
+    }
+
+    /// Verifies the signature on the envelope, failing if it does not match.
+    pub async fn verify(&self, envelope: &Envelope) -> anyhow::Result<()> {
+        let Some(signature) = &envelope.signature else {
+            if self.allow_unsigned.unwrap_or(false) {
+                warn!("Init data is not signed, but unsigned data is allowed");
+                return Ok(());
+            }
+            anyhow::bail!("Init data is not signed");
+        };
+        let message = signed_bytes(envelope)?;
+        let value = data_encoding::BASE64
+            .decode(signature.value.as_bytes())
+            .context("Malformed init data signature")?;
+
+        match signature.algorithm.as_str() {
+            HMAC_SHA256 => {
+                let hmac_key = self
+                    .hmac_key
+                    .as_ref()
+                    .context("Init data is signed with HMAC, but no HMAC key is configured")?;
+                let mut mac = hmac::Hmac::<Sha256>::new_from_slice(&hmac_key.read().await?)?;
+                mac.update(&message);
+                mac.verify_slice(&value)
+                    .map_err(|_| anyhow::anyhow!("Init data HMAC does not match"))?;
+            }
+            ED25519 => {
+                let verifying_key = self.verifying_key().await?;
+                let signature = ed25519_dalek::Signature::from_slice(&value)?;
+                verifying_key
+                    .verify_strict(&message, &signature)
+                    .map_err(|_| anyhow::anyhow!("Init data Ed25519 signature does not match"))?;
+            }
+            other => anyhow::bail!("Unsupported init data signature algorithm: {other}"),
+        }
+        Ok(())
+    }
+
+    async fn verifying_key(&self) -> anyhow::Result<VerifyingKey> {
+        if let Some(public_key) = &self.ed25519_public_key {
+            let pem = String::from_utf8(public_key.read().await?)?;
+            return VerifyingKey::from_public_key_pem(&pem)
+                .map_err(|err| anyhow::anyhow!("Invalid Ed25519 public key: {err}"));
+        }
+        let private_key = self
+            .ed25519_private_key
+            .as_ref()
+            .context("Init data is signed with Ed25519, but no Ed25519 key is configured")?;
+        Ok(signing_key(private_key).await?.verifying_key())
+    }
+}
+
+async fn signing_key(private_key: &KeySource) -> anyhow::Result<SigningKey> {
+    let pem = String::from_utf8(private_key.read().await?)?;
+    SigningKey::from_pkcs8_pem(&pem)
+        .map_err(|err| anyhow::anyhow!("Invalid Ed25519 private key: {err}"))
+}
+
+/// Bytes covered by the signature: the envelope serialized without it.
+fn signed_bytes(envelope: &Envelope) -> anyhow::Result<Vec<u8>> {
+    let unsigned = Envelope {
+        signature: None,
+        ..envelope.clone()
+    };
+    Ok(serde_json::to_vec(&unsigned)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
+    use ed25519_dalek::pkcs8::EncodePrivateKey;
+    use ed25519_dalek::pkcs8::EncodePublicKey;
+
+    use super::*;
+    use crate::vault::models::sys::init::PostInitResponse;
+
+    fn init_data() -> Envelope {
+        Envelope::new(PostInitResponse {
+            keys: vec!["aa".to_owned()],
+            keys_base64: vec!["qg==".to_owned()],
+            root_token: "hvs.root".to_owned(),
+            ..Default::default()
+        })
+    }
+
+    /// Key source reading `contents` from a file of the test's own.
+    fn key_file(name: &str, contents: &[u8]) -> KeySource {
+        let path = std::env::temp_dir().join(format!(
+            "vault-init-integrity-{}-{name}",
+            std::process::id()
+        ));
+        std::fs::write(&path, contents).unwrap();
+        KeySource {
+            file: Some(path),
+            ..Default::default()
+        }
+    }
+
+    fn hmac(name: &str) -> Integrity {
+        Integrity {
+            hmac_key: Some(key_file(name, b"shared secret")),
+            ..Default::default()
+        }
+    }
+
+    fn ed25519(name: &str, seed: u8) -> (Integrity, Integrity) {
+        let signing_key = SigningKey::from_bytes(&[seed; 32]);
+        let private_key = signing_key.to_pkcs8_pem(LineEnding::LF).unwrap();
+        let public_key = signing_key
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .unwrap();
+        let signer = Integrity {
+            ed25519_private_key: Some(key_file(&format!("{name}.key"), private_key.as_bytes())),
+            ..Default::default()
+        };
+        let verifier = Integrity {
+            ed25519_public_key: Some(key_file(&format!("{name}.pub"), public_key.as_bytes())),
+            ..Default::default()
+        };
+        (signer, verifier)
+    }
+
+    #[tokio::test]
+    async fn round_trips_hmac() {
+        let integrity = hmac("hmac");
+        let mut envelope = init_data();
+        integrity.sign(&mut envelope).await.unwrap();
+        assert_eq!(envelope.signature.as_ref().unwrap().algorithm, HMAC_SHA256);
+        integrity.verify(&envelope).await.unwrap();
+
+        let other = Integrity {
+            hmac_key: Some(key_file("hmac-other", b"other secret")),
+            ..Default::default()
+        };
+        assert!(other.verify(&envelope).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn round_trips_ed25519() {
+        let (signer, verifier) = ed25519("ed25519", 1);
+        let mut envelope = init_data();
+        signer.sign(&mut envelope).await.unwrap();
+        assert_eq!(envelope.signature.as_ref().unwrap().algorithm, ED25519);
+
+        // The public key is derived from the private key if not set
+        signer.verify(&envelope).await.unwrap();
+        verifier.verify(&envelope).await.unwrap();
+
+        let (_, other) = ed25519("ed25519-other", 2);
+        assert!(other.verify(&envelope).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_tampered_payload() {
+        for (signer, verifier) in [(hmac("payload"), hmac("payload")), ed25519("payload", 1)] {
+            let mut envelope = init_data();
+            signer.sign(&mut envelope).await.unwrap();
+            envelope
+                .rotate_root_token("hvs.attacker".to_owned())
+                .unwrap();
+            assert!(signer.verify(&envelope).await.is_err());
+            assert!(verifier.verify(&envelope).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_tampered_metadata() {
+        let (signer, _) = ed25519("metadata", 1);
+        for integrity in [hmac("metadata-hmac"), signer] {
+            let mut envelope = init_data();
+            integrity.sign(&mut envelope).await.unwrap();
+            envelope.metadata.cluster_id = Some("other-cluster".to_owned());
+            assert!(integrity.verify(&envelope).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_unsigned_unless_allowed() {
+        let integrity = hmac("unsigned");
+        assert!(integrity.verify(&init_data()).await.is_err());
+
+        let allowing = Integrity {
+            allow_unsigned: Some(true),
+            ..integrity
+        };
+        allowing.verify(&init_data()).await.unwrap();
+
+        // Signed data is still verified
+        let mut envelope = init_data();
+        allowing.sign(&mut envelope).await.unwrap();
+        envelope.metadata.cluster_id = Some("other-cluster".to_owned());
+        assert!(allowing.verify(&envelope).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_mismatched_algorithm() {
+        let (signer, verifier) = ed25519("algorithm", 1);
+        let mut envelope = init_data();
+        hmac("algorithm-hmac").sign(&mut envelope).await.unwrap();
+        assert!(verifier.verify(&envelope).await.is_err());
+
+        let mut envelope = init_data();
+        signer.sign(&mut envelope).await.unwrap();
+        envelope.signature.as_mut().unwrap().algorithm = HMAC_SHA256.to_owned();
+        assert!(hmac("algorithm-hmac").verify(&envelope).await.is_err());
+
+        envelope.signature.as_mut().unwrap().algorithm = "md5".to_owned();
+        assert!(signer.verify(&envelope).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_key() {
+        let mut envelope = init_data();
+        assert!(Integrity::default().sign(&mut envelope).await.is_err());
+
+        hmac("missing").sign(&mut envelope).await.unwrap();
+        assert!(Integrity::default().verify(&envelope).await.is_err());
+
+        let unreadable = Integrity {
+            hmac_key: Some(KeySource {
+                env: Some("VAULT_INIT_TEST_MISSING_HMAC_KEY".to_owned()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(unreadable.verify(&envelope).await.is_err());
+    }
+}
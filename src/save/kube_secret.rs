@@ -3,6 +3,7 @@ use std::collections::BTreeMap;
 use anyhow::Context;
 use k8s_openapi::api::core::v1::Secret;
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use k8s_openapi::ByteString;
 use kube::ResourceExt;
 use serde::Deserialize;
 use serde::Serialize;
@@ -10,9 +11,14 @@ use serde_json::json;
 use tracing::debug;
 use tracing::warn;
 
+use super::Backend;
+use super::Capabilities;
+use super::Envelope;
 use super::Load;
+use super::NotFound;
 use super::Save;
-use crate::vault::models::sys::init::PostInitResponse;
+use super::Version;
+use super::Versioned;
 
 const DEFAULT_SECRET_NAME: &str = "vault-init";
 const DEFAULT_SECRET_KEY: &str = "init.json";
@@ -25,25 +31,80 @@ pub struct KubeSecret {
     pub annotations: Option<BTreeMap<String, String>>,
     pub key: Option<String>,
     pub overwrite: Option<bool>,
+    /// Number of previous versions to retain as `<key>.1`, `<key>.2`, ... in
+    /// the same secret when overwriting.
+    pub history: Option<usize>,
 }
 
-#[async_trait::async_trait]
-impl Save for KubeSecret {
-    async fn save_init(&self, data: &PostInitResponse) -> anyhow::Result<()> {
-        debug!(save_method = "kube_secret", "Saving init data");
-
-        // Create K8s client
+impl KubeSecret {
+    async fn api(&self) -> anyhow::Result<kube::Api<Secret>> {
         let client = kube::Client::try_default().await?;
-        let secrets: kube::Api<Secret> = match &self.namespace {
+        let secrets = match &self.namespace {
             Some(ns) => kube::Api::namespaced(client, ns),
             None => kube::Api::default_namespaced(client),
         };
+        Ok(secrets)
+    }
+
+    fn name(&self) -> String {
+        self.name.clone().unwrap_or(DEFAULT_SECRET_NAME.to_owned())
+    }
 
+    /// Secret key holding the given version, where version 0 is the current
+    /// data.
+    fn version_key(&self, version: usize) -> String {
         let key = self.key.clone().unwrap_or(DEFAULT_SECRET_KEY.to_owned());
+        match version {
+            0 => key,
+            n => format!("{key}.{n}"),
+        }
+    }
+
+    /// Shifts the versions held by `existing` back by one, dropping any beyond
+    /// the configured history.
+    fn rotate_history(&self, existing: &Secret) -> BTreeMap<String, ByteString> {
+        let mut history = BTreeMap::new();
+        let Some(data) = &existing.data else {
+            return history;
+        };
+        for version in 0..self.history.unwrap_or(0) {
+            if let Some(value) = data.get(&self.version_key(version)) {
+                history.insert(self.version_key(version + 1), value.clone());
+            }
+        }
+        history
+    }
+}
+
+impl Backend for KubeSecret {
+    fn capabilities(&self) -> Capabilities {
+        // Replacing the secret is conditional on its resource version
+        Capabilities {
+            versions: self.history.unwrap_or(0) > 0,
+            cas: true,
+            secret_safe: true,
+        }
+    }
+
+    fn versioned(&self) -> Option<&dyn Versioned> {
+        self.capabilities()
+            .versions
+            .then_some(self as &dyn Versioned)
+    }
+}
+
+#[async_trait::async_trait]
+impl Save for KubeSecret {
+    async fn save_init(&self, data: &Envelope) -> anyhow::Result<()> {
+        debug!(save_method = "kube_secret", "Saving init data");
+
+        // Create K8s client
+        let secrets = self.api().await?;
+
         let mut string_data: BTreeMap<String, String> = BTreeMap::new();
-        string_data.insert(key, json!(data).to_string());
+        string_data.insert(self.version_key(0), json!(data).to_string());
 
-        let name = self.name.clone().unwrap_or(DEFAULT_SECRET_NAME.to_owned());
+        let name = self.name();
         let mut secret = Secret {
             metadata: ObjectMeta {
                 name: Some(name.clone()),
@@ -67,6 +128,7 @@ impl Save for KubeSecret {
                 secret = name,
                 "Existing secret found, overwriting"
             );
+            secret.data = Some(self.rotate_history(&existing));
             secret.metadata.resource_version = existing.resource_version();
             secrets
                 .replace(&name, &kube::api::PostParams::default(), &secret)
@@ -83,29 +145,119 @@ impl Save for KubeSecret {
 
 #[async_trait::async_trait]
 impl Load for KubeSecret {
-    async fn load_init(&self) -> anyhow::Result<PostInitResponse> {
+    async fn load_init(&self) -> anyhow::Result<Envelope> {
         debug!(save_method = "kube_secret", "Loading init data");
+        self.load_version(0).await
+    }
+}
 
-        let client = kube::Client::try_default().await?;
-        let secrets: kube::Api<Secret> = match &self.namespace {
-            Some(ns) => kube::Api::namespaced(client, ns),
-            None => kube::Api::default_namespaced(client),
-        };
+#[async_trait::async_trait]
+impl Versioned for KubeSecret {
+    async fn list_versions(&self) -> anyhow::Result<Vec<Version>> {
+        let secret = self.api().await?.get(&self.name()).await?;
+        let data = secret.data.context("Kubernetes secret contained no data")?;
 
-        let name = self.name.clone().unwrap_or(DEFAULT_SECRET_NAME.to_owned());
-        let secret = secrets.get(&name).await?;
+        let mut versions = Vec::new();
+        for version in 0..=self.history.unwrap_or(0) {
+            if let Some(byte_string) = data.get(&self.version_key(version)) {
+                let envelope = Envelope::from_slice(&byte_string.0)?;
+                versions.push(Version { version, envelope });
+            }
+        }
+        Ok(versions)
+    }
 
-        let data = secret.data.context("Kubernetes secret contained no data")?;
+    async fn load_version(&self, version: usize) -> anyhow::Result<Envelope> {
+        let secret = match self.api().await?.get(&self.name()).await {
+            Ok(secret) => secret,
+            Err(kube::Error::Api(response)) if response.code == 404 => {
+                return Err(NotFound.into());
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        let data = secret
+            .data
+            .ok_or(NotFound)
+            .context("Kubernetes secret contained no data")?;
 
-        let key = self.key.clone().unwrap_or(DEFAULT_SECRET_KEY.to_owned());
         let byte_string = data
-            .get(&key)
+            .get(&self.version_key(version))
+            .ok_or(NotFound)
             .context("Kubernetes secret did not contain expected key")?;
 
-        let out = String::from_utf8(byte_string.0.clone())?;
+        Envelope::from_slice(&byte_string.0)
+    }
+
+    async fn restore(&self, data: &Envelope) -> anyhow::Result<()> {
+        let secret = KubeSecret {
+            overwrite: Some(true),
+            ..self.clone()
+        };
+        secret.save_init(data).await
+    }
+}
 
-        let init_response: PostInitResponse = serde_json::from_str(&out)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        Ok(init_response)
+    fn existing(data: &[(&str, &str)]) -> Secret {
+        Secret {
+            data: Some(
+                data.iter()
+                    .map(|(key, value)| ((*key).to_owned(), ByteString(value.as_bytes().to_vec())))
+                    .collect(),
+            ),
+            ..Default::default()
+        }
+    }
+
+    fn values(data: &BTreeMap<String, ByteString>) -> Vec<(&str, &str)> {
+        data.iter()
+            .map(|(key, value)| (key.as_str(), std::str::from_utf8(&value.0).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn names_version_keys() {
+        let secret = KubeSecret {
+            key: Some("data.json".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(secret.version_key(0), "data.json");
+        assert_eq!(secret.version_key(2), "data.json.2");
+        assert_eq!(KubeSecret::default().version_key(1), "init.json.1");
+    }
+
+    #[test]
+    fn rotates_versions_up_to_history() {
+        let secret = KubeSecret {
+            history: Some(2),
+            ..Default::default()
+        };
+        let existing = existing(&[
+            ("init.json", "c"),
+            ("init.json.1", "b"),
+            ("init.json.2", "a"),
+            ("other", "x"),
+        ]);
+        let rotated = secret.rotate_history(&existing);
+        assert_eq!(
+            values(&rotated),
+            [("init.json.1", "c"), ("init.json.2", "b")]
+        );
+    }
+
+    #[test]
+    fn rotates_nothing_without_history() {
+        let existing = existing(&[("init.json", "a")]);
+        assert!(KubeSecret::default().rotate_history(&existing).is_empty());
+        assert!(KubeSecret {
+            history: Some(2),
+            ..Default::default()
+        }
+        .rotate_history(&Secret::default())
+        .is_empty());
     }
 }
@@ -1,17 +1,91 @@
+mod envelope;
 mod file;
+mod integrity;
 mod kube_secret;
+mod registry;
+mod route;
+mod split;
 
+pub use envelope::fingerprint;
+pub use envelope::Envelope;
+pub use envelope::Metadata;
+pub use envelope::Payload;
+pub(crate) use file::sync_parent;
 pub use file::File;
+pub use integrity::Integrity;
 pub use kube_secret::KubeSecret;
-
-use crate::vault::models::sys::init::PostInitResponse;
+pub use registry::Loaded;
+pub use registry::Registry;
+pub use registry::SaveFailed;
+pub use registry::SaveMethods;
+pub use split::Split;
 
 #[async_trait::async_trait]
 pub trait Save {
-    async fn save_init(&self, data: &PostInitResponse) -> anyhow::Result<()>;
+    async fn save_init(&self, data: &Envelope) -> anyhow::Result<()>;
 }
 
 #[async_trait::async_trait]
 pub trait Load {
-    async fn load_init(&self) -> anyhow::Result<PostInitResponse>;
+    /// Reads init data, failing with [`NotFound`] if the save method was read
+    /// but holds none.
+    async fn load_init(&self) -> anyhow::Result<Envelope>;
+}
+
+/// A save method holds no init data, as opposed to failing to be read.
+#[derive(Debug)]
+pub struct NotFound;
+
+impl std::fmt::Display for NotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No init data found")
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+/// A save method that retains previous versions of init data when
+/// overwriting.
+#[async_trait::async_trait]
+pub trait Versioned: Send + Sync {
+    /// Lists the retained versions, starting with the current data as version
+    /// 0 and followed by progressively older ones.
+    async fn list_versions(&self) -> anyhow::Result<Vec<Version>>;
+
+    async fn load_version(&self, version: usize) -> anyhow::Result<Envelope>;
+
+    /// Writes `data` as the current version, retaining the data it replaces
+    /// as version 1. Unlike [`Save::save_init`], this overwrites existing data
+    /// even if the save method is not configured to, so that a previous
+    /// version can be restored.
+    async fn restore(&self, data: &Envelope) -> anyhow::Result<()>;
+}
+
+pub struct Version {
+    pub version: usize,
+    pub envelope: Envelope,
+}
+
+/// A save method that can be built from config by the [`Registry`].
+pub trait Backend: Save + Load + Send + Sync {
+    fn capabilities(&self) -> Capabilities;
+
+    /// Access to retained versions, if this save method keeps them, ie if
+    /// [`Capabilities::versions`] is set.
+    fn versioned(&self) -> Option<&dyn Versioned> {
+        None
+    }
+}
+
+/// What a save method is able to do, so that callers can decide how to use it
+/// without knowing its concrete type.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Retains previous versions of init data, see [`Versioned`].
+    pub versions: bool,
+    /// Detects concurrent modification when overwriting (compare-and-swap).
+    pub cas: bool,
+    /// Stores data in a system intended for secrets, ie with access control
+    /// and encryption at rest.
+    pub secret_safe: bool,
 }
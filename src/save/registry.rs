@@ -0,0 +1,870 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use serde::de::DeserializeOwned;
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use tracing::debug;
+use tracing::warn;
+
+use super::envelope::Payload;
+use super::route;
+use super::route::Holds;
+use super::route::Shares;
+use super::split;
+use super::split::Part;
+use super::split::Split;
+use super::Backend;
+use super::Envelope;
+use super::File;
+use super::Integrity;
+use super::KubeSecret;
+use super::NotFound;
+use super::Versioned;
+use crate::config::Config;
+
+type Factory = Box<dyn Fn(hcl::Value) -> anyhow::Result<Box<dyn Backend>>>;
+
+/// Constructors for save methods, keyed by type. In config, the type is the
+/// label of the block, ie `save_method "<type>" { ... }`, unless the block sets
+/// `type` explicitly.
+pub struct Registry {
+    factories: BTreeMap<String, Factory>,
+}
+
+impl Default for Registry {
+    /// Registry containing the built-in save methods.
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register::<File>("file");
+        registry.register::<KubeSecret>("kube_secret");
+        registry
+    }
+}
+
+impl Registry {
+    /// Empty registry with no save methods.
+    pub fn new() -> Self {
+        Self {
+            factories: BTreeMap::new(),
+        }
+    }
+
+    /// Registers a save method whose config block deserializes into `T`.
+    pub fn register<T>(&mut self, name: &str)
+    where
+        T: Backend + DeserializeOwned + 'static,
+    {
+        let factory: Factory = Box::new(|value: hcl::Value| {
+            let backend = T::deserialize(value.into_deserializer())?;
+            Ok(Box::new(backend))
+        });
+        self.factories.insert(name.to_owned(), factory);
+    }
+
+    /// Builds every save method configured, in the order they appear.
+    pub fn build(&self, config: &Config) -> anyhow::Result<SaveMethods> {
+        let mut destinations = Vec::new();
+        for (name, value) in &config.save_method {
+            // The type defaults to the name, but may be given explicitly so that
+            // a type can be configured more than once
+            let mut value = value.clone();
+            let mut kind = name.clone();
+            let mut shares = None;
+            let mut holds = Holds::All;
+            let mut secret_safe = None;
+            if let hcl::Value::Object(object) = &mut value {
+                match object.shift_remove("type") {
+                    Some(hcl::Value::String(s)) => kind = s,
+                    Some(_) => anyhow::bail!("Save method type must be a string: {name}"),
+                    None => {}
+                }
+                if let Some(indexes) = object.shift_remove("shares") {
+                    let indexes: Vec<usize> = Vec::deserialize(indexes.into_deserializer())
+                        .with_context(|| format!("Invalid key share indexes: {name}"))?;
+                    shares = Some(indexes);
+                }
+                if let Some(value) = object.shift_remove("holds") {
+                    holds = Holds::deserialize(value.into_deserializer()).with_context(|| {
+                        format!("Invalid holds, expected `all`, `keys` or `root_token`: {name}")
+                    })?;
+                }
+                if let Some(value) = object.shift_remove("secret_safe") {
+                    secret_safe = Some(
+                        bool::deserialize(value.into_deserializer())
+                            .with_context(|| format!("Invalid secret_safe: {name}"))?,
+                    );
+                }
+            }
+            if holds == Holds::RootToken && shares.is_some() {
+                anyhow::bail!("Key shares cannot be routed to a save method holding only the root token: {name}");
+            }
+
+            let factory = self
+                .factories
+                .get(&kind)
+                .with_context(|| format!("Unknown save method type: {kind}"))?;
+            let backend = factory(value).with_context(|| format!("Invalid save method: {name}"))?;
+
+            let capabilities = backend.capabilities();
+            debug!(save_method = name, ?capabilities, "Configured save method");
+            if secret_safe == Some(true) && !capabilities.secret_safe {
+                warn!(
+                    save_method = name,
+                    "Save method does not store data in a secret store"
+                );
+            }
+            destinations.push(Destination {
+                name: name.clone(),
+                shares,
+                holds,
+                backend,
+            });
+        }
+
+        if !destinations.is_empty() {
+            if !destinations.iter().any(|d| d.holds.keys()) {
+                anyhow::bail!("No save method holds the key shares");
+            }
+            if !destinations.iter().any(|d| d.holds.root_token()) {
+                anyhow::bail!("No save method holds the root token");
+            }
+        }
+
+        if let Some(split) = &config.split {
+            if split.threshold < 2 || usize::from(split.threshold) > destinations.len() {
+                anyhow::bail!(
+                    "Split threshold must be between 2 and the number of save methods ({})",
+                    destinations.len()
+                );
+            }
+            if destinations
+                .iter()
+                .any(|d| d.shares.is_some() || d.holds != Holds::All)
+            {
+                anyhow::bail!("Key shares cannot be routed to save methods when splitting");
+            }
+        }
+
+        Ok(SaveMethods {
+            destinations,
+            integrity: config.integrity.clone(),
+            split: config.split.clone(),
+        })
+    }
+}
+
+/// The configured save methods, which init data is written to and read from
+/// as a whole.
+pub struct SaveMethods {
+    destinations: Vec<Destination>,
+    integrity: Option<Integrity>,
+    split: Option<Split>,
+}
+
+/// A configured save method.
+struct Destination {
+    name: String,
+    /// Indexes of the key shares routed to this save method, or all of init
+    /// data if not set.
+    shares: Option<Vec<usize>>,
+    holds: Holds,
+    backend: Box<dyn Backend>,
+}
+
+impl Destination {
+    /// Whether a payload read from this save method differs from what would be
+    /// saved to it now. Parts are checked when they are recombined.
+    fn is_stale(&self, payload: &Payload, split: bool) -> bool {
+        match payload {
+            Payload::Data(_) => split || self.shares.is_some() || self.holds != Holds::All,
+            Payload::Part(_) => false,
+            Payload::Shares(shares) => {
+                let expected = match (&self.shares, self.holds) {
+                    (Some(indexes), _) => Some(indexes.clone()),
+                    (None, Holds::Keys) => Some((0..shares.total).collect()),
+                    (None, _) => None,
+                };
+                expected.as_ref() != Some(&shares.indexes)
+                    || shares.data.root_token.is_empty() == self.holds.root_token()
+            }
+            Payload::RootToken(_) => self.holds != Holds::RootToken,
+        }
+    }
+}
+
+/// Init data could not be written to some of the save methods.
+#[derive(Debug)]
+pub struct SaveFailed {
+    /// Save methods that failed, by name.
+    pub failed: Vec<(String, anyhow::Error)>,
+    /// Whether the save methods that were written hold enough to read init
+    /// data back.
+    pub readable: bool,
+}
+
+impl std::fmt::Display for SaveFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed saving init data to")?;
+        for (i, (name, err)) in self.failed.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            write!(f, "{separator}{name} ({err:#})")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SaveFailed {}
+
+/// What to read from save methods.
+#[derive(Clone, Copy)]
+enum Want {
+    All,
+    /// At least this many key shares.
+    KeyShares(usize),
+    RootToken,
+}
+
+impl Want {
+    /// Whether save methods holding `holds` are read.
+    fn reads(self, holds: Holds) -> bool {
+        match self {
+            Want::All => true,
+            Want::KeyShares(_) => holds.keys(),
+            Want::RootToken => holds.root_token(),
+        }
+    }
+}
+
+/// Init data read from save methods.
+pub struct Loaded {
+    pub envelope: Envelope,
+    /// Whether the data should be saved again to match the current config, ie
+    /// to sign or split it.
+    pub stale: bool,
+}
+
+impl SaveMethods {
+    /// Signs init data if integrity protection is enabled, then writes it to
+    /// every save method. If splitting is enabled, each save method is sent
+    /// its own part instead, and save methods with key shares or the root
+    /// token routed to them are sent only those.
+    pub async fn save_init_all(&self, data: &Envelope) -> anyhow::Result<()> {
+        self.save_where(data, |_| true).await
+    }
+
+    /// Writes init data as [`Self::save_init_all`] does, but only to the save
+    /// methods holding the root token, leaving the key shares untouched.
+    pub async fn save_root_token(&self, data: &Envelope) -> anyhow::Result<()> {
+        self.save_where(data, |destination| destination.holds.root_token())
+            .await
+    }
+
+    async fn save_where(
+        &self,
+        data: &Envelope,
+        filter: impl Fn(&Destination) -> bool,
+    ) -> anyhow::Result<()> {
+        let envelopes = match &self.split {
+            Some(split) => split
+                .split(data.data()?, self.destinations.len())?
+                .into_iter()
+                .map(|part| Envelope {
+                    payload: Payload::Part(part),
+                    ..data.clone()
+                })
+                .collect(),
+            None => self.route(data)?,
+        };
+
+        // Every save method is tried, so that one failing does not leave the
+        // others without the data
+        let mut saved = Vec::new();
+        let mut failed = Vec::new();
+        for (destination, mut envelope) in self.destinations.iter().zip(envelopes) {
+            if !filter(destination) {
+                continue;
+            }
+            match self.save_to(destination, &mut envelope).await {
+                Ok(()) => saved.push(destination),
+                Err(err) => {
+                    warn!(
+                        save_method = destination.name,
+                        ?err,
+                        "Failed saving init data"
+                    );
+                    failed.push((destination.name.clone(), err));
+                }
+            }
+        }
+
+        if failed.is_empty() {
+            return Ok(());
+        }
+        Err(SaveFailed {
+            readable: self.readable(data, &saved),
+            failed,
+        }
+        .into())
+    }
+
+    /// Signs an envelope if integrity protection is enabled, and writes it to
+    /// a save method.
+    async fn save_to(
+        &self,
+        destination: &Destination,
+        envelope: &mut Envelope,
+    ) -> anyhow::Result<()> {
+        envelope.signature = None;
+        if let Some(integrity) = &self.integrity {
+            integrity.sign(envelope).await?;
+        }
+        destination.backend.save_init(envelope).await
+    }
+
+    /// Whether init data can be read back from the given save methods alone:
+    /// enough parts to recombine it, or every key share and the root token.
+    fn readable(&self, data: &Envelope, saved: &[&Destination]) -> bool {
+        if let Some(split) = &self.split {
+            return saved.len() >= usize::from(split.threshold);
+        }
+        let Ok(complete) = data.data() else {
+            return false;
+        };
+        let mut covered = vec![false; route::total(complete)];
+        for destination in saved.iter().filter(|d| d.holds.keys()) {
+            match &destination.shares {
+                Some(indexes) => indexes.iter().for_each(|&index| covered[index] = true),
+                None => covered.fill(true),
+            }
+        }
+        covered.iter().all(|covered| *covered) && saved.iter().any(|d| d.holds.root_token())
+    }
+
+    /// The envelope to save to each save method, holding either all of init
+    /// data or the key shares and root token routed to it.
+    fn route(&self, data: &Envelope) -> anyhow::Result<Vec<Envelope>> {
+        let is_routed = |d: &Destination| d.shares.is_some() || d.holds != Holds::All;
+        if !self.destinations.iter().any(is_routed) {
+            return Ok(vec![data.clone(); self.destinations.len()]);
+        }
+
+        let complete = data.data()?;
+        let routes: Option<Vec<&[usize]>> = self
+            .destinations
+            .iter()
+            .filter(|d| d.holds.keys())
+            .map(|d| d.shares.as_deref())
+            .collect();
+        if let Some(routes) = routes {
+            route::check_coverage(complete, routes)?;
+        }
+
+        let all: Vec<usize> = (0..route::total(complete)).collect();
+        self.destinations
+            .iter()
+            .map(|destination| {
+                let payload = match (destination.holds, &destination.shares) {
+                    (Holds::All, None) => return Ok(data.clone()),
+                    (Holds::RootToken, _) => Payload::RootToken(complete.root_token.clone()),
+                    (Holds::All, Some(indexes)) => {
+                        Payload::Shares(Shares::select(complete, indexes)?)
+                    }
+                    (Holds::Keys, indexes) => Payload::Shares(
+                        Shares::select(complete, indexes.as_deref().unwrap_or(&all))?
+                            .without_root_token(),
+                    ),
+                };
+                Ok(Envelope {
+                    payload,
+                    ..data.clone()
+                })
+            })
+            .collect()
+    }
+
+    /// Reads init data from the first save method that has it, or recombines
+    /// it from enough save methods holding parts or key shares of it. Each
+    /// envelope read is verified if integrity protection is enabled, and
+    /// verification failure is fatal rather than falling through to the next
+    /// save method.
+    pub async fn load_init_all(&self) -> anyhow::Result<Envelope> {
+        Ok(self.load().await?.envelope)
+    }
+
+    /// Reads init data as [`Self::load_init_all`] does, also reporting whether
+    /// it is stored differently than it would be saved now. If key shares are
+    /// routed, every one of them and the root token must be read.
+    pub async fn load(&self) -> anyhow::Result<Loaded> {
+        self.gather(Want::All).await
+    }
+
+    /// Reads key shares from save methods until at least `threshold` of them
+    /// are held, which may be fewer than all if they were routed to different
+    /// save methods. If fewer are found, the envelope holds those that were.
+    pub async fn load_key_shares(&self, threshold: usize) -> anyhow::Result<Envelope> {
+        Ok(self.gather(Want::KeyShares(threshold)).await?.envelope)
+    }
+
+    /// Reads the root token, from the save methods holding it only. See
+    /// [`Envelope::root_token`].
+    pub async fn load_root_token(&self) -> anyhow::Result<Envelope> {
+        Ok(self.gather(Want::RootToken).await?.envelope)
+    }
+
+    async fn gather(&self, want: Want) -> anyhow::Result<Loaded> {
+        // Parts found, grouped by the split they belong to
+        let mut splits: Vec<Vec<Envelope>> = Vec::new();
+        let mut held: Option<(Envelope, Shares)> = None;
+        let mut root_token: Option<String> = None;
+        let mut unsigned = false;
+        let mut stale = false;
+
+        for destination in &self.destinations {
+            let name = &destination.name;
+            if !want.reads(destination.holds) {
+                continue;
+            }
+            let envelope = match destination.backend.load_init().await {
+                Ok(envelope) => envelope,
+                Err(err) => {
+                    debug!(save_method = name, ?err, "Failed loading init data");
+                    continue;
+                }
+            };
+            self.verify(&envelope).await?;
+            unsigned |= envelope.signature.is_none();
+            stale |= destination.is_stale(&envelope.payload, self.split.is_some())
+                || (self.integrity.is_some() && unsigned);
+
+            match &envelope.payload {
+                Payload::Data(_) => return Ok(Loaded { envelope, stale }),
+                Payload::RootToken(token) => {
+                    debug!(save_method = name, "Loaded root token");
+                    if matches!(want, Want::RootToken) {
+                        return Ok(Loaded { envelope, stale });
+                    }
+                    root_token.get_or_insert_with(|| token.clone());
+                    match held.take() {
+                        Some((envelope, shares)) if shares.is_complete() => {
+                            return Ok(Loaded {
+                                envelope: held_envelope(envelope, shares, root_token)?,
+                                stale,
+                            });
+                        }
+                        previous => held = previous,
+                    }
+                    continue;
+                }
+                Payload::Shares(shares) => {
+                    debug!(save_method = name, indexes = ?shares.indexes, "Loaded key shares");
+                    if matches!(want, Want::RootToken) {
+                        if shares.data.root_token.is_empty() {
+                            continue;
+                        }
+                        return Ok(Loaded { envelope, stale });
+                    }
+                    let merged = match &held {
+                        Some((previous, _)) if !previous.metadata.same_init(&envelope.metadata) => {
+                            warn!(
+                                save_method = name,
+                                "Ignoring key shares belonging to a different init"
+                            );
+                            continue;
+                        }
+                        Some((_, previous)) => previous.merge(shares)?,
+                        None => shares.clone(),
+                    };
+                    let done = match want {
+                        Want::KeyShares(t) => merged.is_complete() || merged.indexes.len() >= t,
+                        Want::All | Want::RootToken => {
+                            merged.is_complete()
+                                && (!merged.data.root_token.is_empty() || root_token.is_some())
+                        }
+                    };
+                    if done {
+                        return Ok(Loaded {
+                            envelope: held_envelope(envelope, merged, root_token)?,
+                            stale,
+                        });
+                    }
+                    held = Some((envelope, merged));
+                    continue;
+                }
+                Payload::Part(_) => {}
+            }
+            debug!(save_method = name, "Loaded init data part");
+            if let Some(envelopes) = add_part(&mut splits, envelope, name) {
+                return self.combine(envelopes, unsigned);
+            }
+        }
+
+        not_found(want, held, root_token, &splits, stale)
+    }
+
+    fn combine(&self, envelopes: Vec<Envelope>, unsigned: bool) -> anyhow::Result<Loaded> {
+        let parts: Vec<Part> = envelopes
+            .iter()
+            .filter_map(|envelope| match &envelope.payload {
+                Payload::Part(part) => Some(part.clone()),
+                Payload::Data(_) | Payload::Shares(_) | Payload::RootToken(_) => None,
+            })
+            .collect();
+        let data = split::combine(&parts)?;
+
+        let part = &parts[0];
+        let stale = self.split.as_ref().is_none_or(|split| {
+            split.threshold != part.threshold || usize::from(part.parts) != self.destinations.len()
+        }) || (self.integrity.is_some() && unsigned);
+
+        let mut envelope = envelopes
+            .into_iter()
+            .next()
+            .context("No parts to combine")?;
+        envelope.payload = Payload::Data(data);
+        envelope.signature = None;
+        Ok(Loaded { envelope, stale })
+    }
+
+    /// Whether any save method holds init data. Fails if a save method cannot
+    /// be read, as it may hold data that is not to be replaced.
+    pub async fn hold_init(&self) -> anyhow::Result<bool> {
+        for destination in &self.destinations {
+            match destination.backend.load_init().await {
+                Ok(_) => return Ok(true),
+                Err(err) if err.downcast_ref::<NotFound>().is_some() => {}
+                Err(err) => {
+                    return Err(
+                        err.context(format!("Failed reading save method: {}", destination.name))
+                    );
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Reads init data from each save method on its own, without verifying it
+    /// or recombining parts or key shares, by name.
+    pub async fn load_each(&self) -> Vec<(&str, anyhow::Result<Envelope>)> {
+        let mut loaded = Vec::new();
+        for destination in &self.destinations {
+            let envelope = destination.backend.load_init().await;
+            loaded.push((destination.name.as_str(), envelope));
+        }
+        loaded
+    }
+
+    /// Integrity protection of init data, if enabled.
+    pub fn integrity(&self) -> Option<&Integrity> {
+        self.integrity.as_ref()
+    }
+
+    /// Save methods that retain previous versions of init data, by name.
+    pub fn versioned(&self) -> impl Iterator<Item = (&str, &dyn Versioned)> {
+        self.destinations.iter().filter_map(|destination| {
+            Some((destination.name.as_str(), destination.backend.versioned()?))
+        })
+    }
+
+    /// The named save method, which must retain previous versions of init
+    /// data.
+    pub fn versioned_by_name(&self, save_method: &str) -> anyhow::Result<&dyn Versioned> {
+        self.destinations
+            .iter()
+            .find(|destination| destination.name == save_method)
+            .with_context(|| format!("Save method not configured: {save_method}"))?
+            .backend
+            .versioned()
+            .with_context(|| format!("Save method does not retain versions: {save_method}"))
+    }
+
+    /// Makes a retained version of init data current again in the named save
+    /// method. The data being replaced is itself retained as version 1, and is
+    /// overwritten even if the save method is not configured to overwrite.
+    pub async fn restore_version(&self, save_method: &str, version: usize) -> anyhow::Result<()> {
+        let versioned = self.versioned_by_name(save_method)?;
+        let data = versioned.load_version(version).await?;
+        self.verify(&data).await?;
+        versioned.restore(&data).await
+    }
+
+    async fn verify(&self, data: &Envelope) -> anyhow::Result<()> {
+        if let Some(integrity) = &self.integrity {
+            integrity.verify(data).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds a part to the group of parts of the same split, returning the group
+/// once it holds enough parts to recombine.
+fn add_part(
+    splits: &mut Vec<Vec<Envelope>>,
+    envelope: Envelope,
+    save_method: &str,
+) -> Option<Vec<Envelope>> {
+    let Payload::Part(part) = &envelope.payload else {
+        return None;
+    };
+    let threshold = usize::from(part.threshold);
+    let index = splits.iter().position(
+        |group| matches!(&group[0].payload, Payload::Part(first) if first.same_split(part)),
+    );
+    let index = index.unwrap_or_else(|| {
+        if !splits.is_empty() {
+            warn!(
+                save_method,
+                "Found init data parts belonging to different splits"
+            );
+        }
+        splits.push(Vec::new());
+        splits.len() - 1
+    });
+    let group = &mut splits[index];
+    group.push(envelope);
+    (group.len() >= threshold).then(|| std::mem::take(group))
+}
+
+/// Result of reading every save method without finding all of init data: the
+/// key shares that were found, or an error describing what is missing.
+fn not_found(
+    want: Want,
+    held: Option<(Envelope, Shares)>,
+    root_token: Option<String>,
+    splits: &[Vec<Envelope>],
+    stale: bool,
+) -> anyhow::Result<Loaded> {
+    if let Some((envelope, shares)) = held {
+        let msg = if shares.is_complete() {
+            "Found every key share, but not the root token".to_owned()
+        } else {
+            format!(
+                "Found {} of the {} key shares routed to save methods",
+                shares.indexes.len(),
+                shares.total
+            )
+        };
+        // Only unsealing can make do with some of the key shares
+        if !matches!(want, Want::KeyShares(_)) {
+            anyhow::bail!(msg);
+        }
+        warn!(msg);
+        return Ok(Loaded {
+            envelope: held_envelope(envelope, shares, root_token)?,
+            stale,
+        });
+    }
+    // Report on the split closest to being recombined
+    let largest = splits.iter().max_by_key(|group| group.len());
+    if let Some(group) = largest {
+        if let Some(Payload::Part(part)) = group.first().map(|e| &e.payload) {
+            anyhow::bail!(
+                "Found {} of the {} init data parts required to recombine it",
+                group.len(),
+                part.threshold
+            );
+        }
+    }
+    Err(anyhow::anyhow!(
+        "Failed loading init data from all save methods"
+    ))
+}
+
+/// Envelope for key shares gathered from save methods, with the root token if
+/// it was found separately, holding complete init data if every key share and
+/// the root token were found.
+fn held_envelope(
+    envelope: Envelope,
+    mut shares: Shares,
+    root_token: Option<String>,
+) -> anyhow::Result<Envelope> {
+    if let (true, Some(root_token)) = (shares.data.root_token.is_empty(), root_token) {
+        shares.data.root_token = root_token;
+    }
+    let payload = if shares.is_complete() && !shares.data.root_token.is_empty() {
+        Payload::Data(shares.into_data()?)
+    } else {
+        Payload::Shares(shares)
+    };
+    Ok(Envelope {
+        payload,
+        signature: None,
+        ..envelope
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::vault::models::sys::init::PostInitResponse;
+
+    fn init_data() -> Envelope {
+        Envelope::new(PostInitResponse {
+            keys: vec!["aa".to_owned(), "bb".to_owned()],
+            keys_base64: vec!["qg==".to_owned(), "uw==".to_owned()],
+            root_token: "hvs.root".to_owned(),
+            ..Default::default()
+        })
+    }
+
+    /// Empty directory for the files of one test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("vault-init-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Save methods splitting init data across `parts` files in `dir`.
+    fn split_across(dir: &std::path::Path, parts: usize) -> SaveMethods {
+        let mut config = String::from("split {\n  threshold = 2\n}\n");
+        for part in 0..parts {
+            let path = dir.join(format!("part{part}.json"));
+            writeln!(
+                config,
+                "save_method \"part{part}\" {{\n  type = \"file\"\n  path = \"{}\"\n  overwrite = true\n}}",
+                path.display()
+            )
+            .unwrap();
+        }
+        let config: Config = hcl::from_str(&config).unwrap();
+        Registry::default().build(&config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn recombines_split() {
+        let dir = test_dir("recombines-split");
+        let save_methods = split_across(&dir, 3);
+        save_methods.save_init_all(&init_data()).await.unwrap();
+        std::fs::remove_file(dir.join("part0.json")).unwrap();
+
+        let loaded = save_methods.load().await.unwrap();
+        assert_eq!(loaded.envelope.data().unwrap(), init_data().data().unwrap());
+        assert!(!loaded.stale);
+    }
+
+    #[tokio::test]
+    async fn recombines_split_reaching_threshold_first() {
+        let dir = test_dir("mixed-splits");
+        let save_methods = split_across(&dir, 3);
+        save_methods.save_init_all(&init_data()).await.unwrap();
+
+        // The first part read belongs to another split, which must not hide
+        // the two parts of the current one
+        let other = test_dir("mixed-splits-other");
+        split_across(&other, 3)
+            .save_init_all(&init_data())
+            .await
+            .unwrap();
+        std::fs::copy(other.join("part0.json"), dir.join("part0.json")).unwrap();
+
+        let loaded = save_methods.load().await.unwrap();
+        assert_eq!(loaded.envelope.data().unwrap(), init_data().data().unwrap());
+    }
+
+    #[tokio::test]
+    async fn fails_below_threshold() {
+        let dir = test_dir("below-threshold");
+        let save_methods = split_across(&dir, 3);
+        save_methods.save_init_all(&init_data()).await.unwrap();
+        std::fs::remove_file(dir.join("part0.json")).unwrap();
+        std::fs::remove_file(dir.join("part1.json")).unwrap();
+
+        let err = save_methods.load().await.err().unwrap();
+        assert!(err.to_string().contains("Found 1 of the 2"), "{err}");
+    }
+
+    /// Save methods holding one key share each.
+    fn route_across(dir: &std::path::Path) -> SaveMethods {
+        let config = format!(
+            "save_method \"a\" {{\n  type = \"file\"\n  path = \"{}\"\n  shares = [0]\n}}\n\
+             save_method \"b\" {{\n  type = \"file\"\n  path = \"{}\"\n  shares = [1]\n}}\n",
+            dir.join("a.json").display(),
+            dir.join("b.json").display(),
+        );
+        Registry::default()
+            .build(&hcl::from_str(&config).unwrap())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn fails_loading_all_without_every_key_share() {
+        let dir = test_dir("missing-shares");
+        let save_methods = route_across(&dir);
+        save_methods.save_init_all(&init_data()).await.unwrap();
+        assert!(save_methods.load().await.is_ok());
+
+        std::fs::remove_file(dir.join("b.json")).unwrap();
+        let err = save_methods.load().await.err().unwrap();
+        assert!(err.to_string().contains("Found 1 of the 2"), "{err}");
+
+        let loaded = save_methods.load_key_shares(2).await.unwrap();
+        assert_eq!(loaded.key_shares().unwrap().keys, ["aa"]);
+    }
+
+    #[tokio::test]
+    async fn ignores_key_shares_of_different_init() {
+        let dir = test_dir("routed-shares");
+        let save_methods = route_across(&dir);
+        save_methods.save_init_all(&init_data()).await.unwrap();
+
+        let other = test_dir("routed-shares-other");
+        let other_data = Envelope::new(PostInitResponse {
+            keys: vec!["cc".to_owned(), "dd".to_owned()],
+            ..init_data().data().unwrap().clone()
+        });
+        route_across(&other)
+            .save_init_all(&other_data)
+            .await
+            .unwrap();
+        std::fs::copy(other.join("b.json"), dir.join("b.json")).unwrap();
+
+        let loaded = save_methods.load_key_shares(2).await.unwrap();
+        assert_eq!(loaded.key_shares().unwrap().keys, ["aa"]);
+    }
+
+    #[tokio::test]
+    async fn tells_missing_from_unreadable() {
+        let dir = test_dir("hold-init");
+        let save_methods = split_across(&dir, 2);
+        assert!(!save_methods.hold_init().await.unwrap());
+
+        std::fs::create_dir(dir.join("part1.json")).unwrap();
+        assert!(save_methods.hold_init().await.is_err());
+
+        std::fs::remove_dir(dir.join("part1.json")).unwrap();
+        save_methods.save_init_all(&init_data()).await.unwrap();
+        assert!(save_methods.hold_init().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn saves_to_every_save_method_despite_failures() {
+        let dir = test_dir("save-failures");
+        let save_methods = split_across(&dir, 3);
+        std::fs::create_dir(dir.join("part0.json")).unwrap();
+
+        let err = save_methods.save_init_all(&init_data()).await.unwrap_err();
+        let err = err.downcast_ref::<SaveFailed>().unwrap();
+        assert_eq!(err.failed.len(), 1);
+        assert_eq!(err.failed[0].0, "part0");
+        assert!(err.readable);
+        assert!(dir.join("part2.json").exists());
+
+        std::fs::remove_file(dir.join("part2.json")).unwrap();
+        std::fs::create_dir(dir.join("part2.json")).unwrap();
+        let err = save_methods.save_init_all(&init_data()).await.unwrap_err();
+        assert!(!err.downcast_ref::<SaveFailed>().unwrap().readable);
+    }
+
+    #[test]
+    fn rejects_threshold_below_two() {
+        let config: Config = hcl::from_str(
+            "split {\n  threshold = 1\n}\nsave_method \"file\" {\n  path = \"init.json\"\n}\n",
+        )
+        .unwrap();
+        assert!(Registry::default().build(&config).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::vault::models::sys::init::PostInitResponse;
+
+/// Which parts of init data a save method holds, set by its `holds`
+/// attribute.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Holds {
+    #[default]
+    All,
+    /// Key shares, without the root token.
+    Keys,
+    RootToken,
+}
+
+impl Holds {
+    pub fn keys(self) -> bool {
+        self != Holds::RootToken
+    }
+
+    pub fn root_token(self) -> bool {
+        self != Holds::Keys
+    }
+}
+
+/// Some of the key shares from init data, routed to one save method.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shares {
+    /// Positions of the held key shares in the lists returned by init.
+    pub indexes: Vec<usize>,
+    /// Number of key shares returned by init.
+    pub total: usize,
+    /// Init data holding only the key shares at `indexes`, in the same order.
+    /// The root token is empty if it was routed elsewhere.
+    pub data: PostInitResponse,
+}
+
+impl Shares {
+    /// Takes the key shares at `indexes` from complete init data. Both unseal
+    /// and recovery keys are taken, whichever init returned.
+    pub fn select(data: &PostInitResponse, indexes: &[usize]) -> anyhow::Result<Self> {
+        let total = total(data);
+        if let Some(index) = indexes.iter().find(|&&index| index >= total) {
+            anyhow::bail!("Key share index {index} is out of range, init returned {total} shares");
+        }
+
+        let pick = |keys: &[String]| -> Vec<String> {
+            if keys.is_empty() {
+                return Vec::new();
+            }
+            indexes.iter().map(|&index| keys[index].clone()).collect()
+        };
+        Ok(Self {
+            indexes: indexes.to_vec(),
+            total,
+            data: PostInitResponse {
+                keys: pick(&data.keys),
+                keys_base64: pick(&data.keys_base64),
+                recovery_keys: pick(&data.recovery_keys),
+                recovery_keys_base64: pick(&data.recovery_keys_base64),
+                root_token: data.root_token.clone(),
+            },
+        })
+    }
+
+    /// Takes the root token out, to be held by a different save method.
+    pub fn without_root_token(mut self) -> Self {
+        self.data.root_token = String::new();
+        self
+    }
+
+    /// Combines shares held by different save methods, keeping the root token
+    /// of `self` if it has one.
+    pub fn merge(&self, other: &Self) -> anyhow::Result<Self> {
+        if self.total != other.total {
+            anyhow::bail!(
+                "Key shares belong to different init data ({} and {} shares in total)",
+                self.total,
+                other.total
+            );
+        }
+
+        // Columns are the key lists of init data, rows are key share indexes
+        let mut rows: BTreeMap<usize, [Option<String>; 4]> = BTreeMap::new();
+        for shares in [self, other] {
+            for (i, &index) in shares.indexes.iter().enumerate() {
+                let row = rows.entry(index).or_default();
+                let columns = [
+                    &shares.data.keys,
+                    &shares.data.keys_base64,
+                    &shares.data.recovery_keys,
+                    &shares.data.recovery_keys_base64,
+                ];
+                for (cell, keys) in row.iter_mut().zip(columns) {
+                    if cell.is_none() {
+                        *cell = keys.get(i).cloned();
+                    }
+                }
+            }
+        }
+
+        let column =
+            |c: usize| -> Vec<String> { rows.values().filter_map(|row| row[c].clone()).collect() };
+        Ok(Self {
+            indexes: rows.keys().copied().collect(),
+            total: self.total,
+            data: PostInitResponse {
+                keys: column(0),
+                keys_base64: column(1),
+                recovery_keys: column(2),
+                recovery_keys_base64: column(3),
+                root_token: if self.data.root_token.is_empty() {
+                    other.data.root_token.clone()
+                } else {
+                    self.data.root_token.clone()
+                },
+            },
+        })
+    }
+
+    /// Whether every key share is held.
+    pub fn is_complete(&self) -> bool {
+        self.indexes.len() == self.total
+    }
+
+    /// Complete init data, failing if any key share is missing.
+    pub fn into_data(self) -> anyhow::Result<PostInitResponse> {
+        if !self.is_complete() {
+            anyhow::bail!(
+                "Found {} of the {} key shares",
+                self.indexes.len(),
+                self.total
+            );
+        }
+        Ok(self.data)
+    }
+}
+
+/// Checks that every key share of init data is routed to at least one save
+/// method, given the indexes routed to each.
+pub fn check_coverage<'a>(
+    data: &PostInitResponse,
+    routes: impl IntoIterator<Item = &'a [usize]>,
+) -> anyhow::Result<()> {
+    let mut covered = vec![false; total(data)];
+    for indexes in routes {
+        for &index in indexes {
+            *covered
+                .get_mut(index)
+                .with_context(|| format!("Key share index {index} is out of range"))? = true;
+        }
+    }
+
+    let missing: Vec<usize> = (0..covered.len()).filter(|&i| !covered[i]).collect();
+    if !missing.is_empty() {
+        anyhow::bail!("Key shares {missing:?} are not routed to any save method");
+    }
+    Ok(())
+}
+
+/// Number of key shares returned by init.
+pub fn total(data: &PostInitResponse) -> usize {
+    data.keys
+        .len()
+        .max(data.keys_base64.len())
+        .max(data.recovery_keys.len())
+        .max(data.recovery_keys_base64.len())
+}
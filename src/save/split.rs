@@ -0,0 +1,178 @@
+use anyhow::Context;
+use blahaj::Share;
+use blahaj::Sharks;
+use hmac::KeyInit;
+use hmac::Mac;
+use serde::Deserialize;
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::vault::models::sys::init::PostInitResponse;
+
+/// Splits init data locally with Shamir's secret sharing, sending one part to
+/// each save method. Any `threshold` of them are needed to recombine it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Split {
+    pub threshold: u8,
+}
+
+/// One part of split init data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Part {
+    /// Number of parts required to recombine the init data.
+    pub threshold: u8,
+    /// Number of parts the init data was split into.
+    pub parts: u8,
+    /// HMAC of the complete init data keyed by `salt`, to tell apart parts of
+    /// different splits and to check the result of recombining.
+    pub digest: String,
+    /// Base64-encoded random key of the digest, the same for every part of a
+    /// split.
+    pub salt: String,
+    /// Base64-encoded Shamir share.
+    pub share: String,
+}
+
+impl Split {
+    pub fn split(&self, data: &PostInitResponse, parts: usize) -> anyhow::Result<Vec<Part>> {
+        let parts = u8::try_from(parts).context("Too many save methods to split across")?;
+        if self.threshold < 2 || self.threshold > parts {
+            anyhow::bail!(
+                "Split threshold must be between 2 and the number of save methods ({parts})"
+            );
+        }
+
+        let secret = serde_json::to_vec(data)?;
+        let salt: [u8; 32] = rand::random();
+        let digest = digest(&secret, &salt)?;
+        let salt = data_encoding::BASE64.encode(&salt);
+        let parts = Sharks(self.threshold)
+            .dealer(&secret)
+            .take(usize::from(parts))
+            .map(|share| Part {
+                threshold: self.threshold,
+                parts,
+                digest: digest.clone(),
+                salt: salt.clone(),
+                share: data_encoding::BASE64.encode(&Vec::from(&share)),
+            })
+            .collect();
+        Ok(parts)
+    }
+}
+
+impl Part {
+    /// Whether this part belongs to the same split as `other`.
+    pub fn same_split(&self, other: &Part) -> bool {
+        self.digest == other.digest && self.salt == other.salt
+    }
+}
+
+/// Recombines init data from parts of the same split, which must number at
+/// least the threshold.
+pub fn combine(parts: &[Part]) -> anyhow::Result<PostInitResponse> {
+    let first = parts.first().context("No parts to combine")?;
+    let mut shares = Vec::new();
+    for part in parts {
+        if !part.same_split(first) {
+            anyhow::bail!("Parts belong to different splits");
+        }
+        let bytes = data_encoding::BASE64.decode(part.share.as_bytes())?;
+        let share = Share::try_from(bytes.as_slice()).map_err(|err| anyhow::anyhow!("{err}"))?;
+        shares.push(share);
+    }
+
+    let secret = Sharks(first.threshold)
+        .recover(&shares)
+        .map_err(|err| anyhow::anyhow!("{err}"))?;
+    let salt = data_encoding::BASE64.decode(first.salt.as_bytes())?;
+    if digest(&secret, &salt)? != first.digest {
+        anyhow::bail!("Recombined init data does not match its digest");
+    }
+    Ok(serde_json::from_slice(&secret)?)
+}
+
+/// HMAC-SHA256 of `secret` keyed by `salt`, so that the digest stored with
+/// each part cannot be used to test guesses of init data.
+fn digest(secret: &[u8], salt: &[u8]) -> anyhow::Result<String> {
+    let mut mac = hmac::Hmac::<Sha256>::new_from_slice(salt)?;
+    mac.update(secret);
+    Ok(format!(
+        "hmac-sha256:{}",
+        data_encoding::HEXLOWER.encode(&mac.finalize().into_bytes())
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn init_data() -> PostInitResponse {
+        PostInitResponse {
+            keys: vec!["aa".to_owned(), "bb".to_owned()],
+            keys_base64: vec!["qg==".to_owned(), "uw==".to_owned()],
+            root_token: "hvs.root".to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn round_trips_any_threshold_of_parts() {
+        let parts = Split { threshold: 2 }.split(&init_data(), 3).unwrap();
+        assert_eq!(parts.len(), 3);
+        for pair in [[0, 1], [0, 2], [1, 2]] {
+            let chosen = [parts[pair[0]].clone(), parts[pair[1]].clone()];
+            assert_eq!(combine(&chosen).unwrap(), init_data());
+        }
+        assert_eq!(combine(&parts).unwrap(), init_data());
+    }
+
+    #[test]
+    fn rejects_threshold_below_two() {
+        for threshold in [0, 1] {
+            assert!(Split { threshold }.split(&init_data(), 3).is_err());
+        }
+        assert!(Split { threshold: 4 }.split(&init_data(), 3).is_err());
+    }
+
+    #[test]
+    fn rejects_too_few_parts() {
+        let parts = Split { threshold: 2 }.split(&init_data(), 3).unwrap();
+        assert!(combine(&parts[..1]).is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_part() {
+        let mut parts = Split { threshold: 2 }.split(&init_data(), 3).unwrap();
+        let mut share = data_encoding::BASE64
+            .decode(parts[1].share.as_bytes())
+            .unwrap();
+        share[1] ^= 0xff;
+        parts[1].share = data_encoding::BASE64.encode(&share);
+
+        let err = combine(&parts[..2]).unwrap_err();
+        assert!(err.to_string().contains("does not match"), "{err}");
+    }
+
+    #[test]
+    fn rejects_parts_of_different_splits() {
+        let split = Split { threshold: 2 };
+        let first = split.split(&init_data(), 3).unwrap();
+        let second = split.split(&init_data(), 3).unwrap();
+        assert!(!first[0].same_split(&second[1]));
+
+        let err = combine(&[first[0].clone(), second[1].clone()]).unwrap_err();
+        assert!(err.to_string().contains("different splits"), "{err}");
+    }
+
+    #[test]
+    fn salts_each_split() {
+        let split = Split { threshold: 2 };
+        let first = split.split(&init_data(), 2).unwrap();
+        let second = split.split(&init_data(), 2).unwrap();
+        assert!(first[0].digest.starts_with("hmac-sha256:"));
+        assert_eq!(first[0].salt, first[1].salt);
+        assert_ne!(first[0].salt, second[0].salt);
+        assert_ne!(first[0].digest, second[0].digest);
+    }
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use reqwest::header::HeaderMap;
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use super::error::Result;
+use super::VaultClient;
+
+/// A Vault API endpoint, implemented by the type of its request. The request
+/// is sent as the query of `GET` and `DELETE` requests, and as the JSON body
+/// of others.
+pub trait Endpoint: Serialize + Sync {
+    /// Response body, or `IgnoredAny` for endpoints whose response is not
+    /// read, such as those responding without one.
+    type Response: DeserializeOwned;
+
+    const METHOD: Method;
+    /// Path relative to the Vault address.
+    const PATH: &'static str;
+    /// Whether a token is required.
+    const AUTHENTICATED: bool = false;
+}
+
+/// A request to Vault as it passes through middleware, which can be sent any
+/// number of times.
+#[derive(Clone)]
+pub struct Call {
+    pub method: Method,
+    pub url: url::Url,
+    pub headers: HeaderMap,
+    pub body: Option<Vec<u8>>,
+    pub authenticated: bool,
+}
+
+/// A step every request to Vault passes through, in the order added to the
+/// client, before it is sent by the last.
+#[async_trait::async_trait]
+pub trait Middleware: Send + Sync {
+    async fn handle(
+        &self,
+        client: &VaultClient,
+        call: Call,
+        next: Next<'_>,
+    ) -> Result<reqwest::Response>;
+}
+
+/// The middleware after the current one.
+#[derive(Clone, Copy)]
+pub struct Next<'a> {
+    pub(super) client: &'a VaultClient,
+    pub(super) middleware: &'a [Arc<dyn Middleware>],
+}
+
+impl Next<'_> {
+    /// Passes the request on to the next middleware, or sends it after the
+    /// last.
+    pub async fn run(self, call: Call) -> Result<reqwest::Response> {
+        match self.middleware.split_first() {
+            Some((middleware, rest)) => {
+                let next = Next {
+                    client: self.client,
+                    middleware: rest,
+                };
+                middleware.handle(self.client, call, next).await
+            }
+            None => self.client.send(call).await,
+        }
+    }
+}
+
+// Bodies carry keys and tokens, so only their length is shown
+impl std::fmt::Debug for Call {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Call")
+            .field("method", &self.method)
+            .field("url", &self.url.as_str())
+            .field("headers", &self.headers)
+            .field("body_len", &self.body.as_ref().map(Vec::len))
+            .field("authenticated", &self.authenticated)
+            .finish()
+    }
+}
@@ -0,0 +1,162 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+/// A failed request to Vault.
+#[derive(Debug)]
+pub enum Error {
+    /// The endpoint could not be joined to the Vault address.
+    Endpoint(url::ParseError),
+    /// The request could not be encoded.
+    Encode(Box<dyn std::error::Error + Send + Sync>),
+    /// The endpoint requires a token, but the client has none.
+    MissingToken,
+    /// The request could not be sent, or no response was received.
+    Transport(reqwest::Error),
+    /// The request could not be sent over a Unix socket, or no response was
+    /// received.
+    Socket(std::io::Error),
+    /// Vault responded with an error status, and the messages it gave.
+    Status {
+        status: StatusCode,
+        errors: Vec<String>,
+    },
+    /// The response could not be decoded.
+    Decode(serde_json::Error),
+    /// Vault is sealed, so cannot serve the request.
+    Sealed,
+    /// Vault is a standby node, and redirected to the active node.
+    Standby { location: Option<String> },
+    /// The token is invalid, or not allowed to make the request.
+    PermissionDenied { errors: Vec<String> },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Body of Vault's error responses.
+#[derive(Deserialize)]
+struct ErrorResponse {
+    #[serde(default)]
+    errors: Vec<String>,
+}
+
+impl Error {
+    /// Reads the error Vault responded with, if the response is not a success.
+    pub async fn check(response: reqwest::Response) -> Result<reqwest::Response> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        if status.is_redirection() {
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .map(ToOwned::to_owned);
+            return Err(Error::Standby { location });
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        let errors = serde_json::from_str::<ErrorResponse>(&body)
+            .map(|body| body.errors)
+            .unwrap_or_default();
+        Err(match status {
+            StatusCode::FORBIDDEN => Error::PermissionDenied { errors },
+            StatusCode::SERVICE_UNAVAILABLE
+                if errors.iter().any(|error| error.contains("sealed")) =>
+            {
+                Error::Sealed
+            }
+            status => Error::Status { status, errors },
+        })
+    }
+
+    /// Whether Vault refused to initialize because it already is, such as when
+    /// another process initialized it first.
+    pub fn is_already_initialized(&self) -> bool {
+        matches!(
+            self,
+            Error::Status { status, errors }
+                if *status == StatusCode::BAD_REQUEST
+                    && errors.iter().any(|error| error.contains("already initialized"))
+        )
+    }
+
+    /// Whether Vault holds no token with the accessor looked up, such as when
+    /// the token was already revoked or has expired.
+    pub fn is_invalid_accessor(&self) -> bool {
+        matches!(
+            self,
+            Error::Status { status, errors }
+                if *status == StatusCode::BAD_REQUEST
+                    && errors.iter().any(|error| error.contains("invalid accessor"))
+        )
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Endpoint(err) => write!(f, "Invalid Vault endpoint: {err}"),
+            Error::Encode(err) => write!(f, "Failed encoding request to Vault: {err}"),
+            Error::MissingToken => write!(f, "No token is set for an authenticated request"),
+            Error::Transport(err) => write!(f, "Request to Vault failed: {err}"),
+            Error::Socket(err) => write!(f, "Request to Vault over its socket failed: {err}"),
+            Error::Status { status, errors } if errors.is_empty() => {
+                write!(f, "Vault responded with {status}")
+            }
+            Error::Status { status, errors } => {
+                write!(f, "Vault responded with {status}: {}", errors.join("; "))
+            }
+            Error::Decode(err) => write!(f, "Failed decoding response from Vault: {err}"),
+            Error::Sealed => write!(f, "Vault is sealed"),
+            Error::Standby {
+                location: Some(location),
+            } => write!(f, "Vault is a standby node, redirecting to {location}"),
+            Error::Standby { location: None } => write!(f, "Vault is a standby node"),
+            Error::PermissionDenied { errors } if errors.is_empty() => {
+                write!(f, "Vault denied permission")
+            }
+            Error::PermissionDenied { errors } => {
+                write!(f, "Vault denied permission: {}", errors.join("; "))
+            }
+        }
+    }
+}
+
+// Messages of wrapped errors are part of the message, as reqwest's are
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn check(status: u16, body: &'static str) -> Error {
+        let response = hyper::Response::builder()
+            .status(status)
+            .header(
+                reqwest::header::LOCATION,
+                "https://active:8200/v1/sys/rekey",
+            )
+            .body(body)
+            .unwrap();
+        Error::check(response.into()).await.unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn tells_sealed_from_unavailable() {
+        let sealed = check(503, r#"{"errors":["Vault is sealed"]}"#).await;
+        assert!(matches!(sealed, Error::Sealed));
+
+        let unavailable = check(503, r#"{"errors":["standby"]}"#).await;
+        assert!(matches!(unavailable, Error::Status { .. }));
+    }
+
+    #[tokio::test]
+    async fn tells_standby_from_redirect() {
+        let standby = check(307, "").await;
+        assert!(matches!(
+            standby,
+            Error::Standby { location: Some(location) } if location.starts_with("https://active")
+        ));
+    }
+}
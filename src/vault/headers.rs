@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use reqwest::header::HeaderMap;
+use reqwest::header::HeaderName;
+use reqwest::header::HeaderValue;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Headers sent with requests to Vault, set in the `headers` block of the
+/// config file.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Headers {
+    /// Headers sent with every request, such as for a proxy in front of Vault.
+    #[serde(default)]
+    pub custom: BTreeMap<String, String>,
+}
+
+impl Headers {
+    /// The custom headers, with their values kept out of logs.
+    pub fn custom(&self) -> anyhow::Result<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.custom {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .with_context(|| format!("Invalid header name: {name}"))?;
+            let mut value = HeaderValue::from_str(value)
+                .with_context(|| format!("Invalid value of header: {name}"))?;
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+        Ok(headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vault::transport::respond_once;
+    use crate::vault::Proxy;
+    use crate::vault::Retry;
+    use crate::vault::Tls;
+    use crate::vault::VaultClient;
+
+    const SEAL_STATUS: &str =
+        r#"{"type":"shamir","initialized":true,"sealed":true,"t":1,"n":1,"progress":0}"#;
+
+    #[tokio::test]
+    async fn sends_custom_headers_with_requests() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            respond_once(&mut stream, SEAL_STATUS).await
+        });
+
+        let headers = Headers {
+            custom: BTreeMap::from([("X-Proxy-Authorization".to_owned(), "secret".to_owned())]),
+        };
+        let vault = VaultClient::new(
+            url::Url::parse(&format!("http://{addr}")).unwrap(),
+            &Tls::default(),
+            &Retry::default(),
+            &headers,
+            &Proxy::default(),
+        )
+        .unwrap();
+        let seal_status = vault.get_seal_status().await.unwrap();
+        assert!(seal_status.sealed);
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("get /v1/sys/seal-status "), "{request}");
+        assert!(
+            request.contains("\r\nx-proxy-authorization: secret\r\n"),
+            "{request}"
+        );
+        assert!(!request.contains("x-vault-namespace"), "{request}");
+    }
+
+    #[test]
+    fn marks_custom_headers_sensitive() {
+        let headers = Headers {
+            custom: BTreeMap::from([("X-Proxy-Authorization".to_owned(), "secret".to_owned())]),
+        };
+        let custom = headers.custom().unwrap();
+        assert!(custom["x-proxy-authorization"].is_sensitive());
+    }
+
+    #[test]
+    fn rejects_invalid_header() {
+        for (name, value) in [("X Proxy", "secret"), ("X-Proxy", "line\nbreak")] {
+            let headers = Headers {
+                custom: BTreeMap::from([(name.to_owned(), value.to_owned())]),
+            };
+            assert!(headers.custom().is_err(), "{name}: {value}");
+        }
+    }
+}
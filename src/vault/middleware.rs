@@ -0,0 +1,217 @@
+use std::time::Instant;
+
+use reqwest::header::HeaderName;
+use reqwest::header::HeaderValue;
+use secrecy::ExposeSecret;
+use tracing::debug;
+use tracing::warn;
+
+use super::endpoint::Call;
+use super::endpoint::Middleware;
+use super::endpoint::Next;
+use super::error::Error;
+use super::error::Result;
+use super::retry;
+use super::VaultClient;
+
+/// Headers carrying credentials, which are never logged.
+const SENSITIVE_HEADERS: &[&str] = &[
+    "x-vault-token",
+    "x-vault-wrap-token",
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+];
+
+/// Adds the client's token to authenticated requests, which fail without one.
+pub struct Token;
+
+/// Marks headers carrying credentials as sensitive, so that they are left out
+/// of logs.
+pub struct Redact;
+
+/// Retries transient failures with backoff until the client's deadline.
+pub struct RetryTransient;
+
+/// Logs each attempt of a request at debug level.
+pub struct Trace;
+
+#[async_trait::async_trait]
+impl Middleware for Token {
+    async fn handle(
+        &self,
+        client: &VaultClient,
+        mut call: Call,
+        next: Next<'_>,
+    ) -> Result<reqwest::Response> {
+        if call.authenticated {
+            let token = client.token.as_ref().ok_or(Error::MissingToken)?;
+            let value = HeaderValue::from_str(token.expose_secret())
+                .map_err(|err| Error::Encode(Box::new(err)))?;
+            call.headers.insert("X-Vault-Token", value);
+        }
+        next.run(call).await
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for Redact {
+    async fn handle(
+        &self,
+        _client: &VaultClient,
+        mut call: Call,
+        next: Next<'_>,
+    ) -> Result<reqwest::Response> {
+        for name in SENSITIVE_HEADERS {
+            if let Some(value) = call.headers.get_mut(HeaderName::from_static(name)) {
+                value.set_sensitive(true);
+            }
+        }
+        next.run(call).await
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for RetryTransient {
+    async fn handle(
+        &self,
+        client: &VaultClient,
+        call: Call,
+        next: Next<'_>,
+    ) -> Result<reqwest::Response> {
+        // Only requests other than POST are considered idempotent
+        let idempotent = call.method != reqwest::Method::POST;
+        let endpoint = call.url.path().to_owned();
+        let mut backoff = client.policy.backoff();
+        loop {
+            let err = match next.run(call.clone()).await {
+                Ok(response) => return Ok(response),
+                Err(err) => err,
+            };
+            if !retry::is_transient(&err, idempotent) {
+                return Err(err);
+            }
+            let Some(delay) = backoff.next() else {
+                warn!(
+                    phase = "retry",
+                    endpoint, "Request to Vault failed, and its deadline has passed"
+                );
+                return Err(err);
+            };
+            warn!(
+                phase = "retry",
+                endpoint,
+                %err,
+                ?delay,
+                "Request to Vault failed, retrying"
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for Trace {
+    async fn handle(
+        &self,
+        _client: &VaultClient,
+        call: Call,
+        next: Next<'_>,
+    ) -> Result<reqwest::Response> {
+        let method = call.method.clone();
+        let endpoint = call.url.path().to_owned();
+        debug!(phase = "vault", ?call, "Sending request to Vault");
+        let start = Instant::now();
+        let result = next.run(call).await;
+        let elapsed = start.elapsed();
+        match &result {
+            Ok(response) => debug!(
+                phase = "vault",
+                %method,
+                endpoint,
+                status = %response.status(),
+                ?elapsed,
+                "Vault responded"
+            ),
+            Err(err) => debug!(
+                phase = "vault",
+                %method,
+                endpoint,
+                %err,
+                ?elapsed,
+                "Request to Vault failed"
+            ),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use reqwest::header::HeaderMap;
+
+    use super::*;
+    use crate::vault::models::auth::token::PostLookupAccessorRequest;
+    use crate::vault::Headers;
+    use crate::vault::Proxy;
+    use crate::vault::Retry;
+    use crate::vault::Tls;
+
+    /// Records the headers of requests instead of sending them.
+    #[derive(Default)]
+    struct Capture(Mutex<Vec<HeaderMap>>);
+
+    #[async_trait::async_trait]
+    impl Middleware for Capture {
+        async fn handle(
+            &self,
+            _client: &VaultClient,
+            call: Call,
+            _next: Next<'_>,
+        ) -> Result<reqwest::Response> {
+            self.0.lock().unwrap().push(call.headers);
+            Err(Error::MissingToken)
+        }
+    }
+
+    fn client(headers: &Headers) -> VaultClient {
+        VaultClient::new(
+            url::Url::parse("http://127.0.0.1:8200").unwrap(),
+            &Tls::default(),
+            &Retry::default(),
+            headers,
+            &Proxy::default(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn sends_token_only_to_authenticated_endpoints() {
+        let capture = Arc::new(Capture::default());
+        let vault = client(&Headers::default())
+            .with_token("hvs.root".to_owned().into())
+            .with_middleware(capture.clone());
+
+        let _ = vault.get_seal_status().await;
+        let _ = vault.execute(&PostLookupAccessorRequest::default()).await;
+
+        let sent: Vec<_> = capture
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|headers| headers.get("X-Vault-Token").map(HeaderValue::is_sensitive))
+            .collect();
+        assert_eq!(sent, [None, Some(true)]);
+    }
+
+    #[tokio::test]
+    async fn fails_authenticated_requests_without_token() {
+        let vault = client(&Headers::default());
+        let result = vault.execute(&PostLookupAccessorRequest::default()).await;
+        assert!(matches!(result, Err(Error::MissingToken)));
+    }
+}
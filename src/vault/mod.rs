@@ -1,27 +1,148 @@
+mod endpoint;
+mod error;
+mod headers;
+mod middleware;
 pub mod models;
-
-use secrecy::ExposeSecret;
-
-use crate::vault::models::auth::token::*;
-use crate::vault::models::sys::generate_root::*;
-use crate::vault::models::sys::init::*;
-use crate::vault::models::sys::seal_status::*;
-use crate::vault::models::sys::unseal::*;
+mod pin;
+mod retry;
+mod server;
+mod tls;
+mod transport;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+pub use endpoint::Call;
+pub use endpoint::Endpoint;
+pub use endpoint::Middleware;
+pub use endpoint::Next;
+pub use error::Error;
+use error::Result;
+pub use headers::Headers;
+use pin::Pins;
+use reqwest::header::HeaderMap;
+use reqwest::header::HeaderValue;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Method;
+use retry::Backoff;
+use retry::Policy;
+pub use retry::Retry;
+pub use server::Feature;
+pub use server::Flavor;
+pub use server::Server;
+pub use tls::Tls;
+use tracing::warn;
+pub use transport::Proxy;
+use transport::Socket;
+
+use crate::vault::models::auth::token::GetLookupSelfRequest;
+use crate::vault::models::auth::token::GetLookupSelfResponse;
+use crate::vault::models::auth::token::PostLookupAccessorRequest;
+use crate::vault::models::auth::token::PostLookupAccessorResponse;
+use crate::vault::models::auth::token::PostRevokeAccessorRequest;
+use crate::vault::models::auth::token::PostRevokeSelfRequest;
+use crate::vault::models::sys::generate_root::DeleteGenerateRootAttemptRequest;
+use crate::vault::models::sys::generate_root::GetGenerateRootAttemptRequest;
+use crate::vault::models::sys::generate_root::GetGenerateRootAttemptResponse;
+use crate::vault::models::sys::generate_root::PostGenerateRootAttemptRequest;
+use crate::vault::models::sys::generate_root::PostGenerateRootAttemptResponse;
+use crate::vault::models::sys::generate_root::PostGenerateRootUpdateRequest;
+use crate::vault::models::sys::generate_root::PostGenerateRootUpdateResponse;
+use crate::vault::models::sys::ha_status::GetHaStatusRequest;
+use crate::vault::models::sys::ha_status::GetHaStatusResponse;
+use crate::vault::models::sys::health::GetHealthRequest;
+use crate::vault::models::sys::health::GetHealthResponse;
+use crate::vault::models::sys::init::GetInitRequest;
+use crate::vault::models::sys::init::GetInitResponse;
+use crate::vault::models::sys::init::PostInitRequest;
+use crate::vault::models::sys::init::PostInitResponse;
+use crate::vault::models::sys::leader::GetLeaderRequest;
+use crate::vault::models::sys::leader::GetLeaderResponse;
+use crate::vault::models::sys::seal_status::GetSealStatusRequest;
+use crate::vault::models::sys::seal_status::GetSealStatusResponse;
+use crate::vault::models::sys::unseal::PostUnsealRequest;
+use crate::vault::models::sys::unseal::PostUnsealResponse;
+use crate::vault::models::sys::version_history::GetVersionHistoryRequest;
+use crate::vault::models::sys::version_history::GetVersionHistoryResponse;
 
 pub struct VaultClient {
     pub addr: url::Url,
     pub http: reqwest::Client,
     token: Option<secrecy::SecretString>,
+    /// Pins Vault's certificate is checked against, if Vault is reached over
+    /// HTTPS.
+    pins: Option<Arc<Pins>>,
+    policy: Policy,
+    /// Socket requests are sent over instead of HTTP, if Vault is reached over
+    /// a Unix domain socket.
+    socket: Option<Arc<Socket>>,
+    /// Every request passes through these, in order, before it is sent.
+    middleware: Arc<[Arc<dyn Middleware>]>,
 }
 
 impl VaultClient {
-    pub fn new(addr: url::Url) -> Self {
-        let http = reqwest::Client::new();
-        Self {
+    pub fn new(
+        mut addr: url::Url,
+        tls: &Tls,
+        retry: &Retry,
+        headers: &Headers,
+        proxy: &Proxy,
+    ) -> anyhow::Result<Self> {
+        let policy = retry.policy();
+        let mut http = reqwest::Client::builder()
+            .default_headers(headers.custom()?)
+            .timeout(policy.request_timeout)
+            .connect_timeout(policy.request_timeout)
+            // Standby nodes redirect to the active node, which is surfaced as
+            // an error rather than followed with the token
+            .redirect(reqwest::redirect::Policy::none());
+        http = proxy.configure(http)?;
+        let mut pins = None;
+        let mut socket = None;
+        if addr.scheme() == "unix" {
+            if tls.is_set() {
+                warn!(
+                    phase = "start",
+                    %addr,
+                    "TLS settings are ignored, as Vault is reached over a Unix socket"
+                );
+            }
+            let path = addr
+                .to_file_path()
+                .map_err(|()| anyhow::anyhow!("Invalid Unix socket address: {addr}"))?;
+            socket = Some(Arc::new(Socket {
+                path,
+                headers: headers.custom()?,
+                timeout: policy.request_timeout,
+            }));
+            // Endpoints are joined to a placeholder address, as the socket
+            // path is not part of them
+            addr = url::Url::parse("http://localhost/")?;
+        } else if addr.scheme() == "https" {
+            let configured = Arc::new(Pins::new(tls.pin_sha256.clone()));
+            http = tls.configure(http, &mut addr, configured.clone())?;
+            pins = Some(configured);
+        } else if tls.is_set() {
+            warn!(
+                phase = "start",
+                %addr,
+                "TLS settings are ignored, as the Vault address does not use HTTPS"
+            );
+        }
+        Ok(Self {
             addr,
-            http,
+            http: http.build()?,
             token: None,
-        }
+            pins,
+            policy,
+            socket,
+            middleware: Arc::new([
+                Arc::new(middleware::Token),
+                Arc::new(middleware::Redact),
+                Arc::new(middleware::RetryTransient),
+                Arc::new(middleware::Trace),
+            ]),
+        })
     }
 
     pub fn with_token(&self, token: secrecy::SecretString) -> Self {
@@ -29,120 +150,116 @@ impl VaultClient {
             addr: self.addr.clone(),
             http: self.http.clone(),
             token: Some(token),
+            pins: self.pins.clone(),
+            policy: self.policy,
+            socket: self.socket.clone(),
+            middleware: self.middleware.clone(),
         }
     }
 
-    pub async fn read_init_status(&self) -> anyhow::Result<GetInitResponse> {
-        let endpoint = self.addr.join("v1/sys/init")?;
+    /// The same client, retrying requests until `deadline` has passed from
+    /// now.
+    pub fn until(&self, deadline: Duration) -> Self {
+        Self {
+            addr: self.addr.clone(),
+            http: self.http.clone(),
+            token: self.token.clone(),
+            pins: self.pins.clone(),
+            policy: self.policy.until(deadline),
+            socket: self.socket.clone(),
+            middleware: self.middleware.clone(),
+        }
+    }
 
-        let response: GetInitResponse = self
-            .http
-            .get(endpoint)
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?;
+    /// The same client, passing requests through `middleware` after the
+    /// others. Middleware added after retries is run for every attempt.
+    #[cfg(test)]
+    pub fn with_middleware(&self, middleware: Arc<dyn Middleware>) -> Self {
+        Self {
+            addr: self.addr.clone(),
+            http: self.http.clone(),
+            token: self.token.clone(),
+            pins: self.pins.clone(),
+            policy: self.policy,
+            socket: self.socket.clone(),
+            middleware: self
+                .middleware
+                .iter()
+                .cloned()
+                .chain([middleware])
+                .collect(),
+        }
+    }
 
-        Ok(response)
+    /// Delays between polls of a state Vault reaches by itself, ending at the
+    /// deadline of the client.
+    pub fn backoff(&self) -> Backoff {
+        self.policy.backoff()
     }
 
-    pub async fn start_init(&self, request: &PostInitRequest) -> anyhow::Result<PostInitResponse> {
-        let endpoint = self.addr.join("v1/sys/init")?;
+    /// SHA-256 pin of the certificate Vault presented, if it was reached over
+    /// HTTPS with the same certificate every time.
+    pub fn server_pin(&self) -> Option<String> {
+        self.pins.as_ref()?.seen()
+    }
 
-        let response: PostInitResponse = self
-            .http
-            .post(endpoint)
-            .json(request)
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?;
+    /// Refuses every connection to Vault, past and future, whose certificate
+    /// does not match `pin`.
+    pub fn bind_server_pin(&self, pin: &str) -> anyhow::Result<()> {
+        let Some(pins) = &self.pins else {
+            anyhow::bail!("Vault address does not use HTTPS, so its certificate cannot be checked");
+        };
+        pins.bind(pin)
+    }
 
-        Ok(response)
+    pub async fn read_init_status(&self) -> Result<GetInitResponse> {
+        self.execute(&GetInitRequest {}).await
     }
 
-    pub async fn get_seal_status(&self) -> anyhow::Result<GetSealStatusResponse> {
-        let endpoint = self.addr.join("v1/sys/seal-status")?;
+    pub async fn start_init(&self, request: &PostInitRequest) -> Result<PostInitResponse> {
+        self.execute(request).await
+    }
 
-        let response: GetSealStatusResponse = self
-            .http
-            .get(endpoint)
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?;
+    pub async fn get_seal_status(&self) -> Result<GetSealStatusResponse> {
+        self.execute(&GetSealStatusRequest {}).await
+    }
 
-        Ok(response)
+    pub async fn get_health(&self, request: &GetHealthRequest) -> Result<GetHealthResponse> {
+        self.execute(request).await
     }
 
-    pub async fn submit_unseal_key(
-        &self,
-        request: &PostUnsealRequest,
-    ) -> anyhow::Result<PostUnsealResponse> {
-        let endpoint = self.addr.join("v1/sys/unseal")?;
+    pub async fn get_leader(&self) -> Result<GetLeaderResponse> {
+        self.execute(&GetLeaderRequest {}).await
+    }
 
-        let response: PostUnsealResponse = self
-            .http
-            .post(endpoint)
-            .json(request)
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?;
+    pub async fn get_ha_status(&self) -> Result<GetHaStatusResponse> {
+        self.execute(&GetHaStatusRequest {}).await
+    }
 
-        Ok(response)
+    pub async fn get_version_history(&self) -> Result<GetVersionHistoryResponse> {
+        self.execute(&GetVersionHistoryRequest::default()).await
     }
 
-    pub async fn get_generate_root_attempt(
+    pub async fn submit_unseal_key(
         &self,
-    ) -> anyhow::Result<GetGenerateRootAttemptResponse> {
-        let endpoint = self.addr.join("v1/sys/generate-root/attempt")?;
-
-        let response = self
-            .http
-            .get(endpoint)
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?;
+        request: &PostUnsealRequest,
+    ) -> Result<PostUnsealResponse> {
+        self.execute(request).await
+    }
 
-        Ok(response)
+    pub async fn get_generate_root_attempt(&self) -> Result<GetGenerateRootAttemptResponse> {
+        self.execute(&GetGenerateRootAttemptRequest {}).await
     }
 
     pub async fn post_generate_root_attempt(
         &self,
         request: &PostGenerateRootAttemptRequest,
-    ) -> anyhow::Result<PostGenerateRootAttemptResponse> {
-        let endpoint = self.addr.join("v1/sys/generate-root/attempt")?;
-
-        let response = self
-            .http
-            .post(endpoint)
-            .json(request)
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?;
-
-        Ok(response)
+    ) -> Result<PostGenerateRootAttemptResponse> {
+        self.execute(request).await
     }
 
-    pub async fn delete_generate_root_attempt(&self) -> anyhow::Result<()> {
-        let endpoint = self.addr.join("v1/sys/generate-root/attempt")?;
-
-        self.http
-            .delete(endpoint)
-            .send()
-            .await?
-            .error_for_status()?
-            .text()
-            .await?;
+    pub async fn delete_generate_root_attempt(&self) -> Result<()> {
+        self.execute(&DeleteGenerateRootAttemptRequest {}).await?;
 
         Ok(())
     }
@@ -150,50 +267,89 @@ impl VaultClient {
     pub async fn post_generate_root_update(
         &self,
         request: &PostGenerateRootUpdateRequest,
-    ) -> anyhow::Result<PostGenerateRootUpdateResponse> {
-        let endpoint = self.addr.join("v1/sys/generate-root/update")?;
-
-        let response = self
-            .http
-            .post(endpoint)
-            .json(request)
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?;
+    ) -> Result<PostGenerateRootUpdateResponse> {
+        self.execute(request).await
+    }
 
-        Ok(response)
+    pub async fn get_auth_token_lookup_self(&self) -> Result<GetLookupSelfResponse> {
+        self.execute(&GetLookupSelfRequest {}).await
     }
 
-    pub async fn post_auth_token_revoke(&self, request: &PostRevokeRequest) -> anyhow::Result<()> {
-        let endpoint = self.addr.join("v1/auth/token/revoke")?;
+    pub async fn post_auth_token_lookup_accessor(
+        &self,
+        request: &PostLookupAccessorRequest,
+    ) -> Result<PostLookupAccessorResponse> {
+        self.execute(request).await
+    }
 
-        self.http
-            .post(endpoint)
-            .header("X-Vault-Token", self.token.clone().unwrap().expose_secret())
-            .json(request)
-            .send()
-            .await?
-            .error_for_status()?
-            .text()
-            .await?;
+    pub async fn post_auth_token_revoke_accessor(
+        &self,
+        request: &PostRevokeAccessorRequest,
+    ) -> Result<()> {
+        self.execute(request).await?;
 
         Ok(())
     }
 
-    pub async fn post_auth_token_revoke_self(&self) -> anyhow::Result<()> {
-        let endpoint = self.addr.join("v1/auth/token/revoke-self")?;
+    pub async fn post_auth_token_revoke_self(&self) -> Result<()> {
+        self.execute(&PostRevokeSelfRequest {}).await?;
 
-        self.http
-            .post(endpoint)
-            .header("X-Vault-Token", self.token.clone().unwrap().expose_secret())
-            .send()
+        Ok(())
+    }
+
+    /// Sends the request of an endpoint through the middleware, and decodes
+    /// the response.
+    pub async fn execute<E: Endpoint>(&self, request: &E) -> Result<E::Response> {
+        let mut url = self.addr.join(E::PATH).map_err(Error::Endpoint)?;
+        let mut headers = HeaderMap::new();
+        let body = if E::METHOD == Method::GET || E::METHOD == Method::DELETE {
+            let query =
+                serde_urlencoded::to_string(request).map_err(|err| Error::Encode(Box::new(err)))?;
+            if !query.is_empty() {
+                url.set_query(Some(&query));
+            }
+            None
+        } else {
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            Some(serde_json::to_vec(request).map_err(|err| Error::Encode(Box::new(err)))?)
+        };
+        let call = Call {
+            method: E::METHOD,
+            url,
+            headers,
+            body,
+            authenticated: E::AUTHENTICATED,
+        };
+
+        let next = Next {
+            client: self,
+            middleware: &self.middleware,
+        };
+        let body = next
+            .run(call)
             .await?
-            .error_for_status()?
-            .text()
-            .await?;
+            .bytes()
+            .await
+            .map_err(Error::Transport)?;
+        // Endpoints without a response body respond with no content
+        let body = if body.is_empty() { &b"null"[..] } else { &body };
+        serde_json::from_slice(body).map_err(Error::Decode)
+    }
 
-        Ok(())
+    /// Sends a request once, after it has passed through every middleware.
+    async fn send(&self, call: Call) -> Result<reqwest::Response> {
+        if let Some(socket) = &self.socket {
+            let response = socket.execute(call).await.map_err(Error::Socket)?;
+            return Error::check(response).await;
+        }
+        let mut request = self
+            .http
+            .request(call.method, call.url)
+            .headers(call.headers);
+        if let Some(body) = call.body {
+            request = request.body(body);
+        }
+        let response = request.send().await.map_err(Error::Transport)?;
+        Error::check(response).await
     }
 }
@@ -1,7 +1,77 @@
+use reqwest::Method;
+use serde::de::IgnoredAny;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::vault::Endpoint;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetLookupSelfRequest {}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetLookupSelfResponse {
+    pub data: LookupSelfData,
+}
+
+/// Properties of a token. The token itself (`id`) is deliberately left out.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LookupSelfData {
+    pub accessor: String,
+    #[serde(default)]
+    pub display_name: String,
+    #[serde(default)]
+    pub policies: Vec<String>,
+    #[serde(default)]
+    pub ttl: i64,
+    pub expire_time: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostLookupAccessorRequest {
+    pub accessor: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostLookupAccessorResponse {
+    pub data: LookupSelfData,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
-pub struct PostRevokeRequest {
-    pub token: String,
+pub struct PostRevokeAccessorRequest {
+    pub accessor: String,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostRevokeSelfRequest {}
+
+impl Endpoint for GetLookupSelfRequest {
+    type Response = GetLookupSelfResponse;
+
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "v1/auth/token/lookup-self";
+    const AUTHENTICATED: bool = true;
+}
+
+impl Endpoint for PostLookupAccessorRequest {
+    type Response = PostLookupAccessorResponse;
+
+    const METHOD: Method = Method::POST;
+    const PATH: &'static str = "v1/auth/token/lookup-accessor";
+    const AUTHENTICATED: bool = true;
+}
+
+impl Endpoint for PostRevokeAccessorRequest {
+    type Response = IgnoredAny;
+
+    const METHOD: Method = Method::POST;
+    const PATH: &'static str = "v1/auth/token/revoke-accessor";
+    const AUTHENTICATED: bool = true;
+}
+
+impl Endpoint for PostRevokeSelfRequest {
+    type Response = IgnoredAny;
+
+    const METHOD: Method = Method::POST;
+    const PATH: &'static str = "v1/auth/token/revoke-self";
+    const AUTHENTICATED: bool = true;
 }
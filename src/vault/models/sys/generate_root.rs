@@ -1,14 +1,24 @@
+use reqwest::Method;
+use serde::de::IgnoredAny;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::vault::Endpoint;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetGenerateRootAttemptRequest {}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GetGenerateRootAttemptResponse {
     pub started: bool,
     pub nonce: String,
     pub progress: i64,
     pub required: i64,
+    #[serde(default)]
     pub encoded_token: String,
+    #[serde(default)]
     pub pgp_fingerprint: String,
+    #[serde(default)]
     pub otp_length: i64,
     pub complete: bool,
 }
@@ -27,12 +37,18 @@ pub struct PostGenerateRootAttemptResponse {
     pub nonce: String,
     pub progress: i64,
     pub required: i64,
+    #[serde(default)]
     pub encoded_token: String,
+    #[serde(default)]
     pub otp: String,
+    #[serde(default)]
     pub otp_length: i64,
     pub complete: bool,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeleteGenerateRootAttemptRequest {}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PostGenerateRootUpdateRequest {
     /// Specifies a single root key share.
@@ -47,7 +63,81 @@ pub struct PostGenerateRootUpdateResponse {
     pub nonce: String,
     pub progress: i64,
     pub required: i64,
+    #[serde(default)]
     pub pgp_fingerprint: String,
     pub complete: bool,
+    #[serde(default)]
     pub encoded_token: String,
 }
+
+impl Endpoint for GetGenerateRootAttemptRequest {
+    type Response = GetGenerateRootAttemptResponse;
+
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "v1/sys/generate-root/attempt";
+}
+
+impl Endpoint for PostGenerateRootAttemptRequest {
+    type Response = PostGenerateRootAttemptResponse;
+
+    const METHOD: Method = Method::POST;
+    const PATH: &'static str = "v1/sys/generate-root/attempt";
+}
+
+impl Endpoint for DeleteGenerateRootAttemptRequest {
+    type Response = IgnoredAny;
+
+    const METHOD: Method = Method::DELETE;
+    const PATH: &'static str = "v1/sys/generate-root/attempt";
+}
+
+impl Endpoint for PostGenerateRootUpdateRequest {
+    type Response = PostGenerateRootUpdateResponse;
+
+    const METHOD: Method = Method::POST;
+    const PATH: &'static str = "v1/sys/generate-root/update";
+}
+
+impl PostGenerateRootUpdateResponse {
+    /// Recovers the new root token from `encoded_token` using the one-time
+    /// password returned when the attempt was started.
+    pub fn decode_token(&self, otp: &str) -> anyhow::Result<String> {
+        let encoded = self.encoded_token.trim_end_matches('=');
+        let token = data_encoding::BASE64_NOPAD.decode(encoded.as_bytes())?;
+        if token.len() != otp.len() {
+            anyhow::bail!("Encoded root token length does not match OTP length");
+        }
+        let token: Vec<u8> = token.iter().zip(otp.bytes()).map(|(a, b)| a ^ b).collect();
+        Ok(String::from_utf8(token)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(token: &str, otp: &str) -> String {
+        let xored: Vec<u8> = token.bytes().zip(otp.bytes()).map(|(a, b)| a ^ b).collect();
+        data_encoding::BASE64.encode(&xored)
+    }
+
+    #[test]
+    fn decodes_token() {
+        let otp = "0123456789abcdefghijklmnopqr";
+        let token = "hvs.abcdefghijklmnopqrstuvwx";
+        let response = PostGenerateRootUpdateResponse {
+            encoded_token: encode(token, &otp[..token.len()]),
+            ..Default::default()
+        };
+        assert_eq!(response.decode_token(&otp[..token.len()]).unwrap(), token);
+    }
+
+    #[test]
+    fn rejects_otp_of_other_length() {
+        let response = PostGenerateRootUpdateResponse {
+            encoded_token: encode("hvs.abc", "1234567"),
+            ..Default::default()
+        };
+        assert!(response.decode_token("123456").is_err());
+    }
+}
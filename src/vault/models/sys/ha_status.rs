@@ -0,0 +1,34 @@
+use reqwest::Method;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::vault::Endpoint;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetHaStatusRequest {}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetHaStatusResponse {
+    pub nodes: Vec<HaNode>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HaNode {
+    #[serde(default)]
+    pub hostname: String,
+    pub api_address: String,
+    #[serde(default)]
+    pub cluster_address: String,
+    pub active_node: bool,
+    pub last_echo: Option<String>,
+    #[serde(default)]
+    pub version: String,
+}
+
+impl Endpoint for GetHaStatusRequest {
+    type Response = GetHaStatusResponse;
+
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "v1/sys/ha-status";
+    const AUTHENTICATED: bool = true;
+}
@@ -0,0 +1,99 @@
+use reqwest::Method;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::vault::Endpoint;
+
+/// Query parameters of `sys/health`.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetHealthRequest {
+    /// Specifies if being a standby should still return the active status code.
+    pub standbyok: bool,
+    /// Specifies if being a performance standby should still return the active
+    /// status code.
+    pub perfstandbyok: bool,
+    /// Specifies the status code returned by a standby node.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub standbycode: Option<u16>,
+    /// Specifies the status code returned by a performance standby node.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub perfstandbycode: Option<u16>,
+    /// Specifies the status code returned by a DR secondary node.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drsecondarycode: Option<u16>,
+    /// Specifies the status code returned when sealed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sealedcode: Option<u16>,
+    /// Specifies the status code returned when uninitialized.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uninitcode: Option<u16>,
+}
+
+impl GetHealthRequest {
+    /// Responds with success in every state, so that the state can be read
+    /// from the body.
+    pub fn any_state() -> Self {
+        Self {
+            standbyok: true,
+            perfstandbyok: true,
+            standbycode: Some(200),
+            perfstandbycode: Some(200),
+            drsecondarycode: Some(200),
+            sealedcode: Some(200),
+            uninitcode: Some(200),
+        }
+    }
+}
+
+impl Endpoint for GetHealthRequest {
+    type Response = GetHealthResponse;
+
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "v1/sys/health";
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetHealthResponse {
+    pub initialized: bool,
+    pub sealed: bool,
+    pub standby: bool,
+    #[serde(default)]
+    pub performance_standby: bool,
+    pub replication_performance_mode: Option<String>,
+    pub replication_dr_mode: Option<String>,
+    #[serde(default)]
+    pub server_time_utc: i64,
+    #[serde(default)]
+    pub version: String,
+    pub cluster_name: Option<String>,
+    pub cluster_id: Option<String>,
+}
+
+impl GetHealthResponse {
+    /// Whether Vault is a DR secondary, which replicates a primary cluster and
+    /// serves neither generating root nor tokens until promoted.
+    pub fn is_dr_secondary(&self) -> bool {
+        self.replication_dr_mode.as_deref() == Some("secondary")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(replication_dr_mode: Option<&str>) -> GetHealthResponse {
+        GetHealthResponse {
+            initialized: true,
+            replication_dr_mode: replication_dr_mode.map(str::to_owned),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn tells_dr_secondary() {
+        assert!(health(Some("secondary")).is_dr_secondary());
+        assert!(!health(Some("primary")).is_dr_secondary());
+        assert!(!health(Some("disabled")).is_dr_secondary());
+        assert!(!health(None).is_dr_secondary());
+    }
+}
@@ -1,8 +1,13 @@
+use reqwest::Method;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::vault::Endpoint;
 use crate::Args;
 
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetInitRequest {}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GetInitResponse {
     pub initialized: bool,
@@ -37,9 +42,32 @@ impl From<Args> for PostInitRequest {
 
 /// An object including the (possibly encrypted, if `pgp_keys` was provided)
 /// root keys, base 64 encoded root keys and initial root token
+///
+/// When using Auto Unseal, `keys` is empty and the recovery keys are returned
+/// instead (possibly encrypted, if `recovery_pgp_keys` was provided).
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PostInitResponse {
+    #[serde(default)]
     pub keys: Vec<String>,
+    #[serde(default)]
     pub keys_base64: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recovery_keys: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recovery_keys_base64: Vec<String>,
     pub root_token: String,
 }
+
+impl Endpoint for GetInitRequest {
+    type Response = GetInitResponse;
+
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "v1/sys/init";
+}
+
+impl Endpoint for PostInitRequest {
+    type Response = PostInitResponse;
+
+    const METHOD: Method = Method::POST;
+    const PATH: &'static str = "v1/sys/init";
+}
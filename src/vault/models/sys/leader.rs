@@ -0,0 +1,31 @@
+use reqwest::Method;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::vault::Endpoint;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetLeaderRequest {}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetLeaderResponse {
+    pub ha_enabled: bool,
+    pub is_self: bool,
+    pub active_time: Option<String>,
+    pub leader_address: String,
+    #[serde(default)]
+    pub leader_cluster_address: String,
+    #[serde(default)]
+    pub performance_standby: bool,
+    #[serde(default)]
+    pub raft_committed_index: Option<u64>,
+    #[serde(default)]
+    pub raft_applied_index: Option<u64>,
+}
+
+impl Endpoint for GetLeaderRequest {
+    type Response = GetLeaderResponse;
+
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "v1/sys/leader";
+}
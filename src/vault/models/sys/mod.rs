@@ -1,4 +1,8 @@
 pub mod generate_root;
+pub mod ha_status;
+pub mod health;
 pub mod init;
+pub mod leader;
 pub mod seal_status;
 pub mod unseal;
+pub mod version_history;
@@ -1,6 +1,12 @@
+use reqwest::Method;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::vault::Endpoint;
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetSealStatusRequest {}
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GetSealStatusResponse {
     pub r#type: String,
@@ -9,12 +15,34 @@ pub struct GetSealStatusResponse {
     pub t: i64,
     pub n: i64,
     pub progress: i64,
+    #[serde(default)]
     pub nonce: String,
+    #[serde(default)]
     pub version: String,
+    #[serde(default)]
     pub build_date: String,
+    #[serde(default)]
     pub migration: bool,
     pub cluster_name: Option<String>,
     pub cluster_id: Option<String>,
-    pub recovery_seal: bool,
+    /// Not reported by older versions.
+    pub recovery_seal: Option<bool>,
+    #[serde(default)]
     pub storage_type: String,
 }
+
+impl GetSealStatusResponse {
+    /// Whether Vault uses Auto Unseal, so that recovery keys take the place of
+    /// unseal keys. Versions not reporting it are recognized by the seal type.
+    pub fn recovery_seal(&self) -> bool {
+        self.recovery_seal
+            .unwrap_or(!matches!(self.r#type.as_str(), "" | "shamir"))
+    }
+}
+
+impl Endpoint for GetSealStatusRequest {
+    type Response = GetSealStatusResponse;
+
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "v1/sys/seal-status";
+}
@@ -1,6 +1,9 @@
+use reqwest::Method;
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::vault::Endpoint;
+
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PostUnsealRequest {
     pub key: Option<String>,
@@ -14,7 +17,15 @@ pub struct PostUnsealResponse {
     pub t: i64,
     pub n: i64,
     pub progress: i64,
+    #[serde(default)]
     pub version: String,
     pub cluster_name: Option<String>,
     pub cluster_id: Option<String>,
 }
+
+impl Endpoint for PostUnsealRequest {
+    type Response = PostUnsealResponse;
+
+    const METHOD: Method = Method::POST;
+    const PATH: &'static str = "v1/sys/unseal";
+}
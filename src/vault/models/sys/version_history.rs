@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+
+use reqwest::Method;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::vault::Endpoint;
+
+/// Lists the versions the cluster has run, oldest first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetVersionHistoryRequest {
+    pub list: bool,
+}
+
+impl Default for GetVersionHistoryRequest {
+    fn default() -> Self {
+        Self { list: true }
+    }
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GetVersionHistoryResponse {
+    pub data: VersionHistory,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionHistory {
+    #[serde(default)]
+    pub keys: Vec<String>,
+    #[serde(default)]
+    pub key_info: BTreeMap<String, VersionInfo>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionInfo {
+    pub previous_version: Option<String>,
+    pub timestamp_installed: Option<String>,
+    #[serde(default)]
+    pub build_date: String,
+}
+
+impl Endpoint for GetVersionHistoryRequest {
+    type Response = GetVersionHistoryResponse;
+
+    const METHOD: Method = Method::GET;
+    const PATH: &'static str = "v1/sys/version-history";
+    const AUTHENTICATED: bool = true;
+}
@@ -0,0 +1,152 @@
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::SystemTime;
+
+use rustls::client::ServerCertVerified;
+use rustls::client::ServerCertVerifier;
+use rustls::Certificate;
+use rustls::ServerName;
+use sha2::Digest;
+use sha2::Sha256;
+use x509_cert::der::Decode;
+use x509_cert::der::Encode;
+
+/// Identities Vault's certificate must match, and those it was seen with.
+/// Identities are SHA-256 pins, ie the base64 SHA-256 digest of a
+/// certificate's DER-encoded subject public key info.
+#[derive(Default)]
+pub struct Pins {
+    /// Configured pins, of which any certificate in the chain must match one,
+    /// if any are set. The digest of a whole certificate is also accepted.
+    configured: Vec<String>,
+    /// Pin the end-entity certificate must match, once bound to init data.
+    bound: Mutex<Option<String>>,
+    /// Pins of every end-entity certificate that passed verification, so that
+    /// connections made before binding can be checked.
+    seen: Mutex<BTreeSet<String>>,
+}
+
+/// Verifies certificates with `inner`, then checks them against the pins.
+pub struct Verifier {
+    pub inner: Arc<dyn ServerCertVerifier>,
+    pub pins: Arc<Pins>,
+}
+
+/// Accepts any certificate, for when verification is disabled.
+pub struct NoVerifier;
+
+impl Pins {
+    pub fn new(configured: Vec<String>) -> Self {
+        Self {
+            configured,
+            ..Default::default()
+        }
+    }
+
+    /// Pin of the end-entity certificate of every connection so far, if there
+    /// was exactly one.
+    pub fn seen(&self) -> Option<String> {
+        let seen = self.seen.lock().unwrap_or_else(PoisonError::into_inner);
+        match seen.len() {
+            1 => seen.first().cloned(),
+            _ => None,
+        }
+    }
+
+    /// Requires every connection, past and future, to present a certificate
+    /// matching `pin`.
+    pub fn bind(&self, pin: &str) -> anyhow::Result<()> {
+        *self.bound.lock().unwrap_or_else(PoisonError::into_inner) = Some(pin.to_owned());
+        let seen = self.seen.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(other) = seen.iter().find(|seen| *seen != pin) {
+            anyhow::bail!(
+                "Vault presented a certificate with pin {other}, but init data was created with \
+                 {pin}"
+            );
+        }
+        Ok(())
+    }
+
+    fn check(&self, end_entity: &Certificate, intermediates: &[Certificate]) -> anyhow::Result<()> {
+        let pin = spki_sha256(end_entity)?;
+
+        if !self.configured.is_empty() {
+            let mut matched = false;
+            for cert in std::iter::once(end_entity).chain(intermediates) {
+                let cert_pin = data_encoding::BASE64.encode(&Sha256::digest(&cert.0));
+                if self.configured.contains(&spki_sha256(cert)?)
+                    || self.configured.contains(&cert_pin)
+                {
+                    matched = true;
+                    break;
+                }
+            }
+            if !matched {
+                anyhow::bail!("Certificate with pin {pin} does not match any configured pin");
+            }
+        }
+
+        if let Some(bound) = &*self.bound.lock().unwrap_or_else(PoisonError::into_inner) {
+            if *bound != pin {
+                anyhow::bail!(
+                    "Certificate with pin {pin} does not match the pin {bound} init data was \
+                     created with"
+                );
+            }
+        }
+
+        self.seen
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(pin);
+        Ok(())
+    }
+}
+
+impl ServerCertVerifier for Verifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+        self.pins
+            .check(end_entity, intermediates)
+            .map_err(|err| rustls::Error::General(err.to_string()))?;
+        Ok(verified)
+    }
+}
+
+impl ServerCertVerifier for NoVerifier {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// SHA-256 pin of a certificate's subject public key info.
+pub fn spki_sha256(cert: &Certificate) -> anyhow::Result<String> {
+    let cert = x509_cert::Certificate::from_der(&cert.0)?;
+    let spki = cert.tbs_certificate().subject_public_key_info().to_der()?;
+    Ok(data_encoding::BASE64.encode(&Sha256::digest(spki)))
+}
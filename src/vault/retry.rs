@@ -0,0 +1,337 @@
+use std::time::Duration;
+
+use rand::Rng;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::time::Instant;
+
+use super::Error;
+use crate::journal::Step;
+
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_MIN_BACKOFF: Duration = Duration::from_millis(500);
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(10);
+const DEFAULT_READY_DEADLINE: Duration = Duration::from_mins(5);
+const DEFAULT_PHASE_DEADLINE: Duration = Duration::from_mins(1);
+
+/// How requests to Vault are timed out and retried. Durations are in seconds.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Retry {
+    /// Timeout of each request, including connecting.
+    #[serde(default, with = "seconds")]
+    pub request_timeout: Option<Duration>,
+    /// Delay before the first retry, which doubles with each retry after.
+    #[serde(default, with = "seconds")]
+    pub min_backoff: Option<Duration>,
+    /// Longest delay between retries.
+    #[serde(default, with = "seconds")]
+    pub max_backoff: Option<Duration>,
+    #[serde(default)]
+    pub deadline: Deadlines,
+}
+
+/// How long each phase of a run may retry requests for. Requests are not
+/// retried once their phase's deadline has passed.
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Deadlines {
+    /// Waiting for Vault to respond at the start of a run.
+    #[serde(default, with = "seconds")]
+    pub ready: Option<Duration>,
+    #[serde(default, with = "seconds")]
+    pub init: Option<Duration>,
+    #[serde(default, with = "seconds")]
+    pub unseal: Option<Duration>,
+    #[serde(default, with = "seconds")]
+    pub upgrade: Option<Duration>,
+    #[serde(default, with = "seconds")]
+    pub rotate_root: Option<Duration>,
+}
+
+/// Timeouts and backoff applied by a client, and the deadline of the phase it
+/// is used for.
+#[derive(Debug, Clone, Copy)]
+pub struct Policy {
+    pub request_timeout: Duration,
+    min_backoff: Duration,
+    max_backoff: Duration,
+    /// Retries stop at this instant. Without one, a request is retried for
+    /// the default phase deadline after it is first sent.
+    deadline: Option<Instant>,
+}
+
+/// Delays between attempts of a request.
+pub struct Backoff {
+    next: Duration,
+    max: Duration,
+    deadline: Instant,
+}
+
+impl Retry {
+    pub fn policy(&self) -> Policy {
+        Policy {
+            request_timeout: self.request_timeout.unwrap_or(DEFAULT_REQUEST_TIMEOUT),
+            min_backoff: self.min_backoff.unwrap_or(DEFAULT_MIN_BACKOFF),
+            max_backoff: self.max_backoff.unwrap_or(DEFAULT_MAX_BACKOFF),
+            deadline: None,
+        }
+    }
+}
+
+impl Deadlines {
+    pub fn ready(&self) -> Duration {
+        self.ready.unwrap_or(DEFAULT_READY_DEADLINE)
+    }
+
+    /// Deadline of the phase a step of a run belongs to.
+    pub fn step(&self, step: Step) -> Duration {
+        let deadline = match step {
+            Step::Init | Step::Save => self.init,
+            Step::Unseal => self.unseal,
+            Step::Upgrade => self.upgrade,
+            Step::RotateRoot | Step::SaveToken | Step::Revoke | Step::Done => self.rotate_root,
+        };
+        deadline.unwrap_or(DEFAULT_PHASE_DEADLINE)
+    }
+}
+
+impl Policy {
+    /// The same policy, with retries stopping once `deadline` has passed from
+    /// now.
+    pub fn until(self, deadline: Duration) -> Self {
+        Self {
+            deadline: Some(Instant::now() + deadline),
+            ..self
+        }
+    }
+
+    pub fn backoff(&self) -> Backoff {
+        Backoff {
+            next: self.min_backoff,
+            max: self.max_backoff.max(self.min_backoff),
+            deadline: self
+                .deadline
+                .unwrap_or_else(|| Instant::now() + DEFAULT_PHASE_DEADLINE),
+        }
+    }
+}
+
+impl Backoff {
+    /// Delay before the next attempt, or `None` if it would start after the
+    /// deadline. Delays are jittered so that many clients retrying at once
+    /// spread out.
+    pub fn next(&mut self) -> Option<Duration> {
+        let delay = rand::thread_rng().gen_range(self.next / 2..=self.next);
+        self.next = (self.next * 2).min(self.max);
+        (Instant::now() + delay < self.deadline).then_some(delay)
+    }
+}
+
+/// Whether a failed request may succeed if sent again. Requests that are not
+/// idempotent are only retried if Vault cannot have acted on them.
+pub fn is_transient(err: &Error, idempotent: bool) -> bool {
+    match err {
+        // Rate limited, or unavailable while starting
+        Error::Status { status, .. } => {
+            matches!(status.as_u16(), 429 | 503)
+                || idempotent && matches!(status.as_u16(), 502 | 504)
+        }
+        // Connection refused and the like, before anything was sent
+        Error::Transport(err) if !is_tls_failure(err) => {
+            err.is_connect() || idempotent && (err.is_timeout() || err.is_request())
+        }
+        // Before Vault creates its socket, or while it is not listening
+        Error::Socket(err) => {
+            matches!(
+                err.kind(),
+                std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused
+            ) || idempotent
+        }
+        _ => false,
+    }
+}
+
+/// Whether a request failed because the TLS handshake was rejected, such as by
+/// certificate verification, which retrying will not change.
+fn is_tls_failure(err: &reqwest::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(err);
+    while let Some(err) = source {
+        if err.is::<rustls::Error>() {
+            return true;
+        }
+        // IO errors report the source of the error they wrap, not the error
+        source = match err.downcast_ref::<std::io::Error>() {
+            Some(io) if io.get_ref().is_some() => io
+                .get_ref()
+                .map(|inner| inner as &(dyn std::error::Error + 'static)),
+            _ => err.source(),
+        };
+    }
+    false
+}
+
+/// Durations as a number of seconds.
+mod seconds {
+    use std::time::Duration;
+
+    use serde::de::Error;
+    use serde::Deserialize;
+    use serde::Deserializer;
+    use serde::Serialize;
+    use serde::Serializer;
+
+    #[allow(clippy::ref_option)]
+    pub fn serialize<S: Serializer>(
+        value: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.map(|value| value.as_secs_f64()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<f64>::deserialize(deserializer)?
+            .map(|secs| Duration::try_from_secs_f64(secs).map_err(D::Error::custom))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::StatusCode;
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    fn policy(min_backoff: u64, max_backoff: u64) -> Policy {
+        Retry {
+            min_backoff: Some(Duration::from_secs(min_backoff)),
+            max_backoff: Some(Duration::from_secs(max_backoff)),
+            ..Default::default()
+        }
+        .policy()
+    }
+
+    fn status(status: u16) -> Error {
+        Error::Status {
+            status: StatusCode::from_u16(status).unwrap(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn socket(kind: std::io::ErrorKind) -> Error {
+        Error::Socket(kind.into())
+    }
+
+    /// Error of a request to a local port that nothing listens on.
+    async fn connect_error() -> reqwest::Error {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        reqwest::get(format!("http://{addr}/")).await.unwrap_err()
+    }
+
+    /// Error of an HTTPS request to a server that does not speak TLS.
+    async fn tls_error() -> reqwest::Error {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await;
+        });
+        let err = reqwest::get(format!("https://{addr}/")).await.unwrap_err();
+        server.await.unwrap();
+        err
+    }
+
+    #[test]
+    fn doubles_backoff_up_to_max() {
+        let mut backoff = policy(1, 4).backoff();
+        for max in [1, 2, 4, 4, 4] {
+            let max = Duration::from_secs(max);
+            let delay = backoff.next().unwrap();
+            assert!(
+                max / 2 <= delay && delay <= max,
+                "{delay:?} not within {max:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn raises_max_backoff_to_min() {
+        let mut backoff = policy(2, 1).backoff();
+        for _ in 0..3 {
+            assert!(backoff.next().unwrap() >= Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn gives_up_at_deadline() {
+        let mut backoff = policy(1, 10).until(Duration::from_secs(5)).backoff();
+        // Delays of up to 1s, 2s and then 2s to 4s still end before it
+        for _ in 0..3 {
+            assert!(backoff.next().is_some());
+        }
+        // Those of 4s to 8s may not, and of 5s to 10s do not
+        backoff.next();
+        assert_eq!(backoff.next(), None);
+
+        let mut backoff = policy(1, 10).until(Duration::ZERO).backoff();
+        assert_eq!(backoff.next(), None);
+    }
+
+    #[test]
+    fn retries_unavailable_and_rate_limited() {
+        for idempotent in [false, true] {
+            assert!(is_transient(&status(429), idempotent));
+            assert!(is_transient(&status(503), idempotent));
+            assert!(!is_transient(&status(500), idempotent));
+            assert!(!is_transient(&status(400), idempotent));
+            assert!(!is_transient(&Error::Sealed, idempotent));
+            assert!(!is_transient(&Error::MissingToken, idempotent));
+        }
+    }
+
+    #[test]
+    fn retries_gateway_errors_only_if_idempotent() {
+        for code in [502, 504] {
+            assert!(is_transient(&status(code), true));
+            assert!(!is_transient(&status(code), false));
+        }
+    }
+
+    #[test]
+    fn retries_missing_socket() {
+        for idempotent in [false, true] {
+            assert!(is_transient(
+                &socket(std::io::ErrorKind::NotFound),
+                idempotent
+            ));
+            assert!(is_transient(
+                &socket(std::io::ErrorKind::ConnectionRefused),
+                idempotent
+            ));
+        }
+        let reset = socket(std::io::ErrorKind::ConnectionReset);
+        assert!(is_transient(&reset, true));
+        assert!(!is_transient(&reset, false));
+    }
+
+    #[tokio::test]
+    async fn retries_refused_connection() {
+        let err = connect_error().await;
+        assert!(!is_tls_failure(&err));
+        let err = Error::Transport(err);
+        assert!(is_transient(&err, false));
+        assert!(is_transient(&err, true));
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_tls_failure() {
+        let err = tls_error().await;
+        assert!(is_tls_failure(&err));
+        let err = Error::Transport(err);
+        assert!(!is_transient(&err, false));
+        assert!(!is_transient(&err, true));
+    }
+}
@@ -0,0 +1,178 @@
+use serde::Serialize;
+
+use super::models::sys::seal_status::GetSealStatusResponse;
+use super::models::sys::version_history::VersionHistory;
+
+/// Implementation of the Vault API a server runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Flavor {
+    Vault,
+    VaultEnterprise,
+    OpenBao,
+}
+
+/// Release of a server, ignoring pre-release and build metadata.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+/// API support that differs between flavors and versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// Generate root responds with the one-time password the new root token
+    /// is encoded with, rather than requiring one to be sent.
+    GenerateRootOtp,
+    /// `sys/ha-status` lists the nodes of the cluster.
+    HaStatus,
+}
+
+/// The flavor and version of a server, as detected from its seal status, and
+/// its version history where it can be read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Server {
+    pub flavor: Flavor,
+    /// Unknown if the reported version cannot be parsed.
+    pub version: Option<Version>,
+}
+
+impl Server {
+    /// Detects the server from the version it reports. Vault Enterprise
+    /// reports versions like `1.15.0+ent`, and others are taken to be Vault
+    /// until known to be `OpenBao`, as the version alone does not tell them
+    /// apart.
+    pub fn detect(seal_status: &GetSealStatusResponse) -> Self {
+        let reported = seal_status.version.trim().trim_start_matches('v');
+        let version = Version::parse(reported);
+        let flavor = match reported.split_once('+') {
+            Some((_, build)) if build.contains("ent") => Flavor::VaultEnterprise,
+            _ => Flavor::Vault,
+        };
+        Self { flavor, version }
+    }
+
+    /// The server as detected, known to be `OpenBao` if its version history,
+    /// which requires a token to read, has an `openbao` entry.
+    pub fn with_history(self, history: &VersionHistory) -> Self {
+        let openbao = history
+            .keys
+            .iter()
+            .chain(history.key_info.keys())
+            .any(|version| version.to_ascii_lowercase().contains("openbao"));
+        if openbao && self.flavor == Flavor::Vault {
+            Self {
+                flavor: Flavor::OpenBao,
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+
+    /// Whether the server supports `feature`. Servers whose version is
+    /// unknown are assumed to support everything their flavor can.
+    pub fn supports(&self, feature: Feature) -> bool {
+        let at_least = |major, minor| {
+            self.version
+                .is_none_or(|version| (version.major, version.minor) >= (major, minor))
+        };
+        match (feature, self.flavor) {
+            (Feature::GenerateRootOtp | Feature::HaStatus, Flavor::OpenBao) => true,
+            (Feature::GenerateRootOtp | Feature::HaStatus, _) => at_least(1, 10),
+        }
+    }
+}
+
+impl Version {
+    /// Parses the leading `major.minor.patch` of a version, of which the patch
+    /// may be missing.
+    fn parse(version: &str) -> Option<Self> {
+        let release = version.split(['-', '+']).next().unwrap_or_default();
+        let mut parts = release.split('.').map(str::parse::<u64>);
+        Some(Self {
+            major: parts.next()?.ok()?,
+            minor: parts.next()?.ok()?,
+            patch: parts.next().unwrap_or(Ok(0)).ok()?,
+        })
+    }
+}
+
+impl std::fmt::Display for Flavor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Flavor::Vault => write!(f, "Vault"),
+            Flavor::VaultEnterprise => write!(f, "Vault Enterprise"),
+            Flavor::OpenBao => write!(f, "OpenBao"),
+        }
+    }
+}
+
+impl std::fmt::Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(version: &str) -> Server {
+        Server::detect(&GetSealStatusResponse {
+            version: version.to_owned(),
+            ..Default::default()
+        })
+    }
+
+    fn history(keys: &[&str]) -> VersionHistory {
+        VersionHistory {
+            keys: keys.iter().map(|&key| key.to_owned()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn detects_enterprise_from_version() {
+        let server = detect("1.15.0+ent");
+        assert_eq!(server.flavor, Flavor::VaultEnterprise);
+        assert!(server.supports(Feature::HaStatus));
+    }
+
+    #[test]
+    fn takes_unknown_flavor_as_vault() {
+        for version in ["1.15.0", "2.1.0", "v2.0.0-beta1", "unknown"] {
+            assert_eq!(detect(version).flavor, Flavor::Vault, "{version}");
+        }
+        assert!(detect("2.0.0").supports(Feature::GenerateRootOtp));
+        assert!(!detect("1.9.0").supports(Feature::HaStatus));
+    }
+
+    #[test]
+    fn detects_openbao_from_history() {
+        let server = detect("2.3.1").with_history(&history(&["1.15.0", "openbao-2.3.1"]));
+        assert_eq!(server.flavor, Flavor::OpenBao);
+
+        let server = detect("2.3.1").with_history(&history(&["1.15.0", "2.3.1"]));
+        assert_eq!(server.flavor, Flavor::Vault);
+
+        let server = detect("1.15.0+ent").with_history(&history(&["OpenBao"]));
+        assert_eq!(server.flavor, Flavor::VaultEnterprise);
+    }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(
+            detect("v1.10").version,
+            Some(Version {
+                major: 1,
+                minor: 10,
+                patch: 0
+            })
+        );
+        assert_eq!(detect("1.15.2-rc1+ent").version.unwrap().patch, 2);
+        assert_eq!(detect("dev").version, None);
+    }
+}
@@ -0,0 +1,330 @@
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::Context;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::Resolve;
+use reqwest::dns::Resolving;
+use rustls::client::ServerCertVerifier;
+use rustls::client::WebPkiVerifier;
+use serde::Deserialize;
+use serde::Serialize;
+use tracing::debug;
+use tracing::warn;
+
+use super::pin::NoVerifier;
+use super::pin::Pins;
+use super::pin::Verifier;
+
+/// TLS settings for connecting to Vault, named after the environment variables
+/// of the Vault CLI. Each can also be set in the `tls` block of the config
+/// file, which the command line and environment take precedence over.
+#[derive(clap::Args, Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Tls {
+    /// PEM-encoded CA certificate file to verify Vault's certificate with,
+    /// instead of the built-in roots.
+    #[clap(long, env = "VAULT_CACERT", global = true)]
+    pub ca_cert: Option<PathBuf>,
+
+    /// Directory of PEM-encoded CA certificate files to verify Vault's
+    /// certificate with, instead of the built-in roots.
+    #[clap(long, env = "VAULT_CAPATH", global = true)]
+    pub ca_path: Option<PathBuf>,
+
+    /// PEM-encoded client certificate file for TLS authentication to Vault.
+    #[clap(
+        long,
+        env = "VAULT_CLIENT_CERT",
+        global = true,
+        requires = "client_key"
+    )]
+    pub client_cert: Option<PathBuf>,
+
+    /// PEM-encoded private key file matching the client certificate.
+    #[clap(
+        long,
+        env = "VAULT_CLIENT_KEY",
+        global = true,
+        requires = "client_cert"
+    )]
+    pub client_key: Option<PathBuf>,
+
+    /// Name to verify Vault's certificate against, instead of the host of the
+    /// Vault address.
+    #[clap(long = "tls-server-name", env = "VAULT_TLS_SERVER_NAME", global = true)]
+    pub server_name: Option<String>,
+
+    /// Disable verification of Vault's certificate. Insecure, only for testing.
+    #[clap(
+        long = "tls-skip-verify",
+        env = "VAULT_SKIP_VERIFY",
+        global = true,
+        num_args = 0..=1,
+        default_missing_value = "true",
+        value_parser = clap::builder::BoolishValueParser::new()
+    )]
+    pub skip_verify: Option<bool>,
+
+    /// SHA-256 pins that Vault's certificate chain must match one of, as the
+    /// base64 digest of a certificate's subject public key info, or of the
+    /// whole certificate.
+    #[clap(
+        long = "tls-pin-sha256",
+        env = "VAULT_TLS_PIN_SHA256",
+        global = true,
+        value_delimiter = ','
+    )]
+    #[serde(default)]
+    pub pin_sha256: Vec<String>,
+}
+
+impl Tls {
+    /// Settings from `self`, falling back to `other` for those not set.
+    pub fn or(self, other: Tls) -> Tls {
+        Tls {
+            ca_cert: self.ca_cert.or(other.ca_cert),
+            ca_path: self.ca_path.or(other.ca_path),
+            client_cert: self.client_cert.or(other.client_cert),
+            client_key: self.client_key.or(other.client_key),
+            server_name: self.server_name.or(other.server_name),
+            skip_verify: self.skip_verify.or(other.skip_verify),
+            pin_sha256: if self.pin_sha256.is_empty() {
+                other.pin_sha256
+            } else {
+                self.pin_sha256
+            },
+        }
+    }
+
+    /// Applies the settings to an HTTP client for Vault at `addr`, which is
+    /// changed to use the server name if one is set. Certificates are checked
+    /// against `pins`.
+    pub fn configure(
+        &self,
+        mut builder: reqwest::ClientBuilder,
+        addr: &mut url::Url,
+        pins: Arc<Pins>,
+    ) -> anyhow::Result<reqwest::ClientBuilder> {
+        let mut roots = rustls::RootCertStore::empty();
+        let mut certs = Vec::new();
+        if let Some(path) = &self.ca_cert {
+            certs.push((path.clone(), read_pem_certs(path)?));
+        }
+        if let Some(path) = &self.ca_path {
+            certs.extend(read_ca_path(path)?);
+        }
+        if certs.is_empty() {
+            roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+                rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    ta.subject,
+                    ta.spki,
+                    ta.name_constraints,
+                )
+            }));
+        }
+        for (path, certs) in certs {
+            for cert in certs {
+                roots
+                    .add(&cert)
+                    .with_context(|| format!("Invalid CA certificate: {}", path.display()))?;
+            }
+        }
+
+        let inner: Arc<dyn ServerCertVerifier> = if self.skip_verify.unwrap_or(false) {
+            warn!(
+                phase = "start",
+                "!!! TLS certificate verification is DISABLED, connections to Vault can be \
+                 intercepted and keys stolen. Never use this outside of testing !!!"
+            );
+            Arc::new(NoVerifier)
+        } else {
+            Arc::new(WebPkiVerifier::new(roots, None))
+        };
+        let config = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(Verifier { inner, pins }));
+
+        let mut config = match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => {
+                let certs = read_pem_certs(cert)?;
+                let key = read_pem_key(key)?;
+                config
+                    .with_single_cert(certs, key)
+                    .context("Invalid client certificate or key")?
+            }
+            (Some(_), None) => anyhow::bail!("Client certificate is set without a client key"),
+            (None, Some(_)) => anyhow::bail!("Client key is set without a client certificate"),
+            (None, None) => config.with_no_client_auth(),
+        };
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        builder = builder.use_preconfigured_tls(config);
+
+        if let Some(server_name) = &self.server_name {
+            // The certificate is verified against the host of the URL, so the
+            // URL is given the server name, which is resolved to the addresses
+            // of the original host when connecting
+            let host = addr
+                .host_str()
+                .context("Vault address has no host")?
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .to_owned();
+            addr.set_host(Some(server_name))
+                .with_context(|| format!("Invalid TLS server name: {server_name}"))?;
+            builder = builder.dns_resolver(Arc::new(ServerNameResolver {
+                server_name: server_name.clone(),
+                host,
+            }));
+        }
+
+        Ok(builder)
+    }
+
+    /// Whether any setting is given, which only apply to HTTPS.
+    pub fn is_set(&self) -> bool {
+        self.ca_cert.is_some()
+            || self.ca_path.is_some()
+            || self.client_cert.is_some()
+            || self.server_name.is_some()
+            || self.skip_verify.unwrap_or(false)
+            || !self.pin_sha256.is_empty()
+    }
+}
+
+/// Resolves the TLS server name to the addresses of the host it stands in for,
+/// and any other name as itself.
+struct ServerNameResolver {
+    server_name: String,
+    host: String,
+}
+
+impl ServerNameResolver {
+    fn host<'a>(&'a self, name: &'a str) -> &'a str {
+        if name == self.server_name {
+            &self.host
+        } else {
+            name
+        }
+    }
+}
+
+impl Resolve for ServerNameResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = self.host(name.as_str()).to_owned();
+        Box::pin(async move {
+            // The port is replaced by that of the URL
+            let addrs: Vec<_> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Certificates of the files in a CA directory, in a stable order. Files that
+/// hold no PEM certificate are skipped, like the Vault CLI does.
+fn read_ca_path(path: &Path) -> anyhow::Result<Vec<(PathBuf, Vec<rustls::Certificate>)>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(path)
+        .with_context(|| format!("Failed reading CA directory: {}", path.display()))?
+    {
+        let entry = entry?;
+        if entry.path().is_file() {
+            files.push(entry.path());
+        }
+    }
+    files.sort();
+
+    let mut certs = Vec::new();
+    for file in files {
+        match read_pem_certs(&file) {
+            Ok(file_certs) => certs.push((file, file_certs)),
+            Err(e) => debug!(phase = "start", "Skipping CA file: {e:#}"),
+        }
+    }
+    if certs.is_empty() {
+        anyhow::bail!("No CA certificate found in: {}", path.display());
+    }
+    Ok(certs)
+}
+
+fn read_pem_certs(path: &Path) -> anyhow::Result<Vec<rustls::Certificate>> {
+    let pem = std::fs::read(path)
+        .with_context(|| format!("Failed reading certificate: {}", path.display()))?;
+    let certs = rustls_pemfile::certs(&mut pem.as_slice())
+        .with_context(|| format!("Invalid certificate: {}", path.display()))?;
+    if certs.is_empty() {
+        anyhow::bail!("No certificate found: {}", path.display());
+    }
+    Ok(certs.into_iter().map(rustls::Certificate).collect())
+}
+
+fn read_pem_key(path: &Path) -> anyhow::Result<rustls::PrivateKey> {
+    let pem = std::fs::read(path)
+        .with_context(|| format!("Failed reading client key: {}", path.display()))?;
+    for item in rustls_pemfile::read_all(&mut pem.as_slice())
+        .with_context(|| format!("Invalid client key: {}", path.display()))?
+    {
+        match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => return Ok(rustls::PrivateKey(key)),
+            _ => {}
+        }
+    }
+    anyhow::bail!("No private key found: {}", path.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("vault-init-tls-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // Only the PEM framing is parsed when reading, not the certificate
+    const PEM: &str = "-----BEGIN CERTIFICATE-----\nMAA=\n-----END CERTIFICATE-----\n";
+
+    #[test]
+    fn skips_non_pem_ca_files() {
+        let dir = test_dir("skip");
+        std::fs::write(dir.join("b.pem"), PEM).unwrap();
+        std::fs::write(dir.join("a.pem"), PEM).unwrap();
+        std::fs::write(dir.join("README"), "Not a certificate").unwrap();
+        std::fs::create_dir(dir.join("sub")).unwrap();
+
+        let certs = read_ca_path(&dir).unwrap();
+        let files: Vec<_> = certs.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(files, vec![dir.join("a.pem"), dir.join("b.pem")]);
+        assert!(certs.iter().all(|(_, certs)| certs.len() == 1));
+    }
+
+    #[test]
+    fn fails_ca_path_without_certificates() {
+        let dir = test_dir("empty");
+        std::fs::write(dir.join("README"), "Not a certificate").unwrap();
+        assert!(read_ca_path(&dir).is_err());
+    }
+
+    #[tokio::test]
+    async fn resolves_server_name_to_host() {
+        let resolver = ServerNameResolver {
+            server_name: "vault.example".into(),
+            host: "127.0.0.1".into(),
+        };
+        assert_eq!(resolver.host("vault.example"), "127.0.0.1");
+        assert_eq!(resolver.host("proxy.example"), "proxy.example");
+
+        let addrs: Vec<_> = resolver
+            .resolve("vault.example".parse().unwrap())
+            .await
+            .unwrap()
+            .collect();
+        assert_eq!(addrs.len(), 1);
+        assert_eq!(addrs[0].ip().to_string(), "127.0.0.1");
+    }
+}
@@ -0,0 +1,381 @@
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use reqwest::header::HeaderMap;
+use reqwest::header::HeaderValue;
+use serde::Deserialize;
+use serde::Serialize;
+
+use super::endpoint::Call;
+
+/// Proxy for requests to Vault over HTTP and HTTPS. Unless set, the
+/// `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY` environment variables are used.
+#[allow(clippy::struct_field_names)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct Proxy {
+    /// URL of the proxy for every request.
+    pub url: Option<String>,
+    /// Comma-separated hosts, domains and networks not to use the proxy for,
+    /// in the format of `NO_PROXY`, which is used if not set. Applies to the
+    /// proxies in the environment if `url` is not set.
+    pub no_proxy: Option<String>,
+    /// Ignore proxies set in the environment.
+    pub disabled: Option<bool>,
+}
+
+/// A Unix domain socket Vault listens on, connected to for each request.
+pub struct Socket {
+    pub path: PathBuf,
+    /// Headers sent with every request.
+    pub headers: HeaderMap,
+    pub timeout: Duration,
+}
+
+impl Proxy {
+    pub fn configure(
+        &self,
+        builder: reqwest::ClientBuilder,
+    ) -> anyhow::Result<reqwest::ClientBuilder> {
+        self.configure_with(builder, |name| std::env::var(name).ok())
+    }
+
+    /// Configures the proxy, reading the environment with `env`.
+    fn configure_with(
+        &self,
+        mut builder: reqwest::ClientBuilder,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> anyhow::Result<reqwest::ClientBuilder> {
+        if self.disabled.unwrap_or(false) {
+            return Ok(builder.no_proxy());
+        }
+        let no_proxy = match (&self.url, &self.no_proxy) {
+            (_, Some(no_proxy)) => reqwest::NoProxy::from_string(no_proxy),
+            (Some(_), None) => reqwest::NoProxy::from_env(),
+            (None, None) => return Ok(builder),
+        };
+        if let Some(url) = &self.url {
+            return Ok(builder.proxy(reqwest::Proxy::all(url)?.no_proxy(no_proxy)));
+        }
+
+        // Adding a proxy replaces those reqwest reads from the environment, so
+        // they are read here as it would, but with `no_proxy` applied
+        let from_env = |names: [&str; 2]| {
+            names
+                .into_iter()
+                .find_map(&env)
+                .filter(|url| !url.trim().is_empty())
+        };
+        // Set by the client in CGI, so cannot be trusted there
+        let cgi = env("REQUEST_METHOD").is_some();
+        if let Some(url) = from_env(["HTTP_PROXY", "http_proxy"]).filter(|_| !cgi) {
+            let proxy =
+                reqwest::Proxy::http(&url).with_context(|| format!("Invalid HTTP_PROXY: {url}"))?;
+            builder = builder.proxy(proxy.no_proxy(no_proxy.clone()));
+        }
+        if let Some(url) = from_env(["HTTPS_PROXY", "https_proxy"]) {
+            let proxy = reqwest::Proxy::https(&url)
+                .with_context(|| format!("Invalid HTTPS_PROXY: {url}"))?;
+            builder = builder.proxy(proxy.no_proxy(no_proxy));
+        }
+        Ok(builder)
+    }
+}
+
+impl Socket {
+    /// Sends a request over the socket. Connection failures keep the kind of
+    /// the error, such as when the socket does not exist yet.
+    pub async fn execute(&self, call: Call) -> io::Result<reqwest::Response> {
+        tokio::time::timeout(self.timeout, self.send(call))
+            .await
+            .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::TimedOut, "Request timed out")))
+    }
+
+    async fn send(&self, call: Call) -> io::Result<reqwest::Response> {
+        let stream = tokio::net::UnixStream::connect(&self.path).await?;
+        let (mut sender, connection) = hyper::client::conn::handshake(stream)
+            .await
+            .map_err(io::Error::other)?;
+        tokio::spawn(connection);
+
+        let mut headers = self.headers.clone();
+        headers.extend(call.headers);
+        headers.insert(reqwest::header::HOST, HeaderValue::from_static("localhost"));
+        let mut builder = hyper::Request::builder()
+            .method(call.method)
+            .uri(&call.url[url::Position::BeforePath..]);
+        if let Some(builder_headers) = builder.headers_mut() {
+            *builder_headers = headers;
+        }
+        let request = builder
+            .body(hyper::Body::from(call.body.unwrap_or_default()))
+            .map_err(io::Error::other)?;
+
+        let response = sender
+            .send_request(request)
+            .await
+            .map_err(io::Error::other)?;
+        let (parts, body) = response.into_parts();
+        let body = hyper::body::to_bytes(body)
+            .await
+            .map_err(io::Error::other)?;
+        Ok(hyper::Response::from_parts(parts, body.to_vec()).into())
+    }
+}
+
+/// Reads one request from `stream` and responds with `body` as JSON, returning
+/// the request head in lowercase, for tests.
+#[cfg(test)]
+pub(crate) async fn respond_once<S>(stream: &mut S, body: &str) -> String
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::AsyncReadExt;
+    use tokio::io::AsyncWriteExt;
+
+    let mut request = Vec::new();
+    let mut buf = [0; 1024];
+    while !request.ends_with(b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await.unwrap();
+        assert!(read > 0, "connection closed before end of request head");
+        request.extend_from_slice(&buf[..read]);
+    }
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await.unwrap();
+    String::from_utf8(request).unwrap().to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio::net::UnixListener;
+
+    use super::*;
+
+    /// A server on a local port, and its address.
+    async fn listen() -> (TcpListener, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        (listener, addr)
+    }
+
+    /// Serves one request, returning its head.
+    async fn serve_once(listener: TcpListener) -> String {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        respond_once(&mut stream, "{}").await
+    }
+
+    /// Head of a request to `url`, as received by `listener`.
+    async fn received(
+        proxy: &Proxy,
+        env: &[(&str, &str)],
+        listener: TcpListener,
+        url: &str,
+    ) -> String {
+        let env = |name: &str| {
+            env.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| (*value).to_owned())
+        };
+        let client = proxy
+            .configure_with(reqwest::Client::builder(), env)
+            .unwrap()
+            .build()
+            .unwrap();
+        let server = tokio::spawn(serve_once(listener));
+        let response = client.get(url).send().await.unwrap();
+        assert!(response.status().is_success());
+        server.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn sends_through_configured_proxy() {
+        let (proxy_listener, proxy_addr) = listen().await;
+        let proxy = Proxy {
+            url: Some(format!("http://{proxy_addr}")),
+            ..Default::default()
+        };
+        let request = received(
+            &proxy,
+            &[],
+            proxy_listener,
+            "http://vault.invalid:8200/v1/sys/health",
+        )
+        .await;
+        assert!(
+            request.starts_with("get http://vault.invalid:8200/v1/sys/health "),
+            "{request}"
+        );
+    }
+
+    #[tokio::test]
+    async fn bypasses_configured_proxy_for_no_proxy() {
+        let (_proxy_listener, proxy_addr) = listen().await;
+        let (vault_listener, vault_addr) = listen().await;
+        let proxy = Proxy {
+            url: Some(format!("http://{proxy_addr}")),
+            no_proxy: Some("127.0.0.1".to_owned()),
+            ..Default::default()
+        };
+        let request = received(
+            &proxy,
+            &[],
+            vault_listener,
+            &format!("http://{vault_addr}/v1/sys/health"),
+        )
+        .await;
+        assert!(request.starts_with("get /v1/sys/health "), "{request}");
+    }
+
+    #[tokio::test]
+    async fn applies_no_proxy_to_environment_proxies() {
+        let (proxy_listener, proxy_addr) = listen().await;
+        let (vault_listener, vault_addr) = listen().await;
+        let proxy = Proxy {
+            no_proxy: Some("127.0.0.1".to_owned()),
+            ..Default::default()
+        };
+        let env = [("http_proxy", proxy_addr.as_str())];
+
+        let request = received(
+            &proxy,
+            &env,
+            vault_listener,
+            &format!("http://{vault_addr}/"),
+        )
+        .await;
+        assert!(request.starts_with("get / "), "{request}");
+        let request = received(&proxy, &env, proxy_listener, "http://vault.invalid/").await;
+        assert!(
+            request.starts_with("get http://vault.invalid/ "),
+            "{request}"
+        );
+    }
+
+    #[tokio::test]
+    async fn ignores_http_proxy_in_cgi() {
+        let (_proxy_listener, proxy_addr) = listen().await;
+        let (vault_listener, vault_addr) = listen().await;
+        let proxy = Proxy {
+            no_proxy: Some("vault.invalid".to_owned()),
+            ..Default::default()
+        };
+        let env = [
+            ("HTTP_PROXY", proxy_addr.as_str()),
+            ("REQUEST_METHOD", "GET"),
+        ];
+        let request = received(
+            &proxy,
+            &env,
+            vault_listener,
+            &format!("http://{vault_addr}/"),
+        )
+        .await;
+        assert!(request.starts_with("get / "), "{request}");
+    }
+
+    #[tokio::test]
+    async fn ignores_proxies_when_disabled() {
+        let (_proxy_listener, proxy_addr) = listen().await;
+        let (vault_listener, vault_addr) = listen().await;
+        let proxy = Proxy {
+            url: Some(format!("http://{proxy_addr}")),
+            disabled: Some(true),
+            ..Default::default()
+        };
+        let request = received(
+            &proxy,
+            &[],
+            vault_listener,
+            &format!("http://{vault_addr}/"),
+        )
+        .await;
+        assert!(request.starts_with("get / "), "{request}");
+    }
+
+    #[test]
+    fn rejects_invalid_environment_proxy() {
+        let proxy = Proxy {
+            no_proxy: Some("localhost".to_owned()),
+            ..Default::default()
+        };
+        let env = |name: &str| (name == "HTTPS_PROXY").then(|| "http://[::1".to_owned());
+        assert!(proxy
+            .configure_with(reqwest::Client::builder(), env)
+            .is_err());
+    }
+
+    fn socket_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "vault-init-transport-{}-{name}.sock",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn call(path: &str) -> Call {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Vault-Token", HeaderValue::from_static("hvs.root"));
+        Call {
+            method: reqwest::Method::GET,
+            url: url::Url::parse("http://localhost/")
+                .unwrap()
+                .join(path)
+                .unwrap(),
+            headers,
+            body: None,
+            authenticated: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_over_unix_socket() {
+        let path = socket_path("round-trip");
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            respond_once(&mut stream, r#"{"sealed":true}"#).await
+        });
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Proxy-Authorization", HeaderValue::from_static("secret"));
+        let socket = Socket {
+            path: path.clone(),
+            headers,
+            timeout: Duration::from_secs(5),
+        };
+
+        let response = socket.execute(call("v1/sys/seal-status")).await.unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(response.text().await.unwrap(), r#"{"sealed":true}"#);
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("get /v1/sys/seal-status "), "{request}");
+        for header in [
+            "host: localhost",
+            "x-proxy-authorization: secret",
+            "x-vault-token: hvs.root",
+        ] {
+            assert!(request.contains(&format!("\r\n{header}\r\n")), "{request}");
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn keeps_error_kind_of_missing_socket() {
+        let socket = Socket {
+            path: socket_path("missing"),
+            headers: HeaderMap::new(),
+            timeout: Duration::from_secs(5),
+        };
+        let err = socket
+            .execute(call("v1/sys/seal-status"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -14,3 +14,8 @@ save_method "kube_secret" {
     "myanno.pbar.me" = "annotation is cool"
   }
 }
+
+# Keys of this test cluster are disposable
+emergency {
+  disabled = true
+}